@@ -0,0 +1,107 @@
+use crate::{error::BoardError, Board, Move, Tile, Turn};
+
+pub struct BoardBuilder {
+    size: u8,
+    turn: Turn,
+    komi: f32,
+    tiles: Vec<((usize, usize), Tile)>,
+}
+
+impl Default for BoardBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BoardBuilder {
+    pub fn new() -> Self {
+        Self {
+            size: 5,
+            turn: Turn::Black,
+            komi: 0.0,
+            tiles: Vec::new(),
+        }
+    }
+
+    pub fn size(mut self, size: u8) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn turn(mut self, turn: Turn) -> Self {
+        self.turn = turn;
+        self
+    }
+
+    pub fn komi(mut self, komi: f32) -> Self {
+        self.komi = komi;
+        self
+    }
+
+    pub fn black(self, coords: &[(usize, usize)]) -> Self {
+        self.place(coords, Tile::Black)
+    }
+
+    pub fn white(self, coords: &[(usize, usize)]) -> Self {
+        self.place(coords, Tile::White)
+    }
+
+    pub fn dead(self, coords: &[(usize, usize)]) -> Self {
+        self.place(coords, Tile::Dead)
+    }
+
+    fn place(mut self, coords: &[(usize, usize)], tile: Tile) -> Self {
+        self.tiles.extend(coords.iter().map(|&c| (c, tile)));
+        self
+    }
+
+    pub fn build(self) -> Result<Board, BoardError> {
+        let size = self.size as usize;
+        let mut rep = vec![Tile::Free; size.pow(2)];
+
+        for ((x, y), tile) in self.tiles {
+            if x >= size || y >= size {
+                return Err(BoardError::OutOfBounds(Move::Coords((x, y))));
+            }
+
+            let p = x * size + y;
+            if rep[p] != Tile::Free {
+                return Err(BoardError::Occupied(Move::Coords((x, y))));
+            }
+            rep[p] = tile;
+        }
+
+        Board::from_rep(
+            rep.iter().map(|t| t.to_char()).collect(),
+            self.size,
+            self.turn,
+            self.komi,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use evaluation::Heuristic;
+
+    use super::*;
+
+    #[test]
+    fn builds_the_same_board_as_its_rep() {
+        let built = BoardBuilder::new()
+            .size(3)
+            .turn(Turn::White)
+            .komi(2.5)
+            .black(&[(0, 1), (1, 0)])
+            .white(&[(2, 2)])
+            .dead(&[(1, 1)])
+            .build()
+            .unwrap();
+        let parsed = Board::from_rep(".X.X#...O".to_string(), 3, Turn::White, 2.5).unwrap();
+
+        assert_eq!(built.get_rep(), parsed.get_rep());
+        assert_eq!(built.get_hash(), parsed.get_hash());
+        assert_eq!(built.turn, parsed.turn);
+        assert_eq!(built.komi, parsed.komi);
+    }
+}
@@ -0,0 +1,49 @@
+use std::fmt;
+
+use crate::Move;
+
+// Moves are kept as they were given, except Occupied and Suicide which have already been
+// converted to a placement
+#[derive(Clone, Debug, PartialEq)]
+pub enum BoardError {
+    OutOfBounds(Move),
+    Occupied(Move),
+    Suicide(Move),
+    Repetition,
+    WrongTurn,
+    GameOver(Move),
+    NoMoveToUndo,
+    GameNotOver,
+    InvalidKomi(f32),
+    SizeMismatch(u8, u8),
+    SnapshotAhead,
+    SnapshotDiverged,
+    InvalidRep(String),
+    InvalidBytes(String),
+}
+
+impl fmt::Display for BoardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BoardError::OutOfBounds(mv) => write!(f, "Out of bounds ({:?})", mv),
+            BoardError::Occupied(mv) => write!(f, "Tile is occupied ({:?})", mv),
+            BoardError::Suicide(mv) => write!(f, "Suicide ({:?})", mv),
+            BoardError::Repetition => f.write_str("Repetition"),
+            BoardError::WrongTurn => f.write_str("Not your turn"),
+            BoardError::GameOver(mv) => write!(f, "Game is over ({:?})", mv),
+            BoardError::NoMoveToUndo => f.write_str("No move to undo"),
+            BoardError::GameNotOver => f.write_str("The game is not over"),
+            BoardError::InvalidKomi(komi) => write!(f, "Invalid komi {}", komi),
+            BoardError::SizeMismatch(size, other) => {
+                write!(f, "Size mismatch: {} against {}", size, other)
+            }
+            BoardError::SnapshotAhead => f.write_str("Snapshot is ahead of the current position"),
+            BoardError::SnapshotDiverged => f.write_str("Position diverged from the snapshot"),
+            BoardError::InvalidRep(message) | BoardError::InvalidBytes(message) => {
+                f.write_str(message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BoardError {}
@@ -1,11 +1,20 @@
 use std::collections::hash_map::DefaultHasher;
 use std::collections::{HashSet, VecDeque};
+use std::fmt;
 use std::hash::{Hash, Hasher};
-use std::usize;
 
+use error::BoardError;
 use evaluation::Heuristic;
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
+pub mod builder;
+pub mod error;
+pub mod replay;
+pub mod sgf;
+pub mod spec;
+pub mod symmetry;
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Tile {
     White,
@@ -34,7 +43,8 @@ impl Tile {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Turn {
     White,
     Black,
@@ -49,6 +59,7 @@ impl Turn {
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Option<Self> {
         match s.to_lowercase().trim() {
             "white" => Some(Turn::White),
@@ -77,13 +88,43 @@ impl Turn {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum HeuristicMode {
+    #[default]
+    AreaStrict,
+    AreaLiberties,
+    Influence,
+}
+
+const LIBERTY_WEIGHT: f32 = 0.25;
+const BYTES_HEADER: usize = 6;
+// IPvGO gives White 5.5 komi in a standard game
+pub const DEFAULT_KOMI: f32 = 5.5;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Move {
     Place(usize),
     Coords((usize, usize)),
     Pass,
 }
 
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GroupLiberties {
+    pub groups: usize,
+    pub atari: usize,
+    pub liberties: usize,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LibertySummary {
+    pub black: GroupLiberties,
+    pub white: GroupLiberties,
+}
+
 #[derive(Clone, Debug)]
 pub struct Chain {
     pub id: usize,
@@ -93,6 +134,28 @@ pub struct Chain {
     pub adjacent: HashSet<usize>,
 }
 
+impl Chain {
+    pub fn liberty_count(&self) -> usize {
+        self.liberties.len()
+    }
+
+    // One liberty left, the next move on it captures the chain
+    pub fn is_in_atari(&self) -> bool {
+        self.liberty_count() == 1
+    }
+
+    pub fn size(&self) -> usize {
+        self.positions.len()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GameResult {
+    pub winner: Turn,
+    pub margin: f32,
+}
+
 #[derive(Clone, Debug)]
 pub enum Mod {
     Assignment((usize, usize)),
@@ -104,11 +167,27 @@ pub enum Mod {
 pub struct MoveChange {
     pub action: Move,
     pub previous_turn: Turn,
+    pub previous_stones: (usize, usize),
+    pub previous_territory: (usize, usize),
     pub board_hash: u64,
 
     pub mods: Vec<Mod>,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BoardSnapshot {
+    pub history_len: usize,
+    pub board_hash: u64,
+    pub turn: Turn,
+}
+
+#[derive(Clone, Debug)]
+pub struct Compaction {
+    pub history_len: usize,
+    pub chain_count: usize,
+    pub kept: Vec<usize>,
+}
+
 pub struct Board {
     pub size: u8,
     pub komi: f32,
@@ -116,6 +195,14 @@ pub struct Board {
     pub pos_to_chain: Vec<Option<usize>>,
     pub chains: Vec<Option<Chain>>,
     pub history: Vec<MoveChange>,
+    pub compactions: Vec<Compaction>,
+    pub heuristic_mode: HeuristicMode,
+    // The color the search maximizes for, scores are reported from its perspective
+    pub maximizing: Tile,
+    // (black, white) stones on the board, kept up to date by apply_move and undo_move
+    pub stones: (usize, usize),
+    // (black, white) territory points, only the regions bordering a move are recounted
+    pub territory: (usize, usize),
 }
 
 impl Hash for Board {
@@ -127,6 +214,24 @@ impl Hash for Board {
     }
 }
 
+// Chain ids depend on move order, so boards are equal by their tiles
+impl PartialEq for Board {
+    fn eq(&self, other: &Self) -> bool {
+        self.size == other.size
+            && self.turn == other.turn
+            && self.komi.to_bits() == other.komi.to_bits()
+            && self.iter_tiles().eq(other.iter_tiles())
+    }
+}
+
+impl Eq for Board {}
+
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render_grid())
+    }
+}
+
 impl Clone for Board {
     fn clone(&self) -> Self {
         Self {
@@ -136,6 +241,11 @@ impl Clone for Board {
             chains: self.chains.clone(),
             history: self.history.clone(),
             pos_to_chain: self.pos_to_chain.clone(),
+            compactions: self.compactions.clone(),
+            heuristic_mode: self.heuristic_mode,
+            maximizing: self.maximizing,
+            stones: self.stones,
+            territory: self.territory,
         }
     }
 }
@@ -150,6 +260,11 @@ impl Board {
             pos_to_chain: vec![None; total],
             chains: Vec::new(),
             history: Vec::new(),
+            compactions: Vec::new(),
+            heuristic_mode: HeuristicMode::default(),
+            maximizing: Tile::Black,
+            stones: (0, 0),
+            territory: (0, 0),
         }
     }
 
@@ -162,21 +277,364 @@ impl Board {
     }
 
     fn neighbors(&self, pos: usize) -> Vec<usize> {
+        self.orthogonal_neighbors(pos).collect()
+    }
+
+    fn offset_neighbors(
+        &self,
+        pos: usize,
+        offsets: [(isize, isize); 4],
+    ) -> impl Iterator<Item = usize> + '_ {
         let (x, y) = self.to_coords(pos);
-        let mut nbrs = Vec::new();
-        if x > 0 {
-            nbrs.push(self.to_pos(x - 1, y));
+        let size = self.size as usize;
+        offsets.into_iter().filter_map(move |(dx, dy)| {
+            let nx = x.checked_add_signed(dx).filter(|&n| n < size)?;
+            let ny = y.checked_add_signed(dy).filter(|&n| n < size)?;
+            Some(self.to_pos(nx, ny))
+        })
+    }
+
+    pub fn orthogonal_neighbors(&self, pos: usize) -> impl Iterator<Item = usize> + '_ {
+        self.offset_neighbors(pos, [(-1, 0), (1, 0), (0, -1), (0, 1)])
+    }
+
+    pub fn diagonal_neighbors(&self, pos: usize) -> impl Iterator<Item = usize> + '_ {
+        self.offset_neighbors(pos, [(-1, -1), (-1, 1), (1, -1), (1, 1)])
+    }
+
+    // A copy with another side to move, the history is kept as it is
+    pub fn with_turn(&self, turn: Turn) -> Result<Board, BoardError> {
+        let passes = self.history.iter().rev().take(2);
+        let over =
+            self.turn == Turn::None || passes.filter(|c| c.action == Move::Pass).count() == 2;
+        if turn == Turn::None && !over {
+            return Err(BoardError::GameNotOver);
+        }
+
+        let mut board = self.clone();
+        board.turn = turn;
+        Ok(board)
+    }
+
+    // Legal moves as if color were to move, repetition is still checked against the real history
+    pub fn moves_for(&self, color: Tile) -> impl Iterator<Item = Move> + '_ {
+        let playable = matches!(color, Tile::Black | Tile::White);
+        playable
+            .then(|| {
+                // A position can only come back with the same stone counts. A placement without
+                // a capture adds one stone, so the full superko check is only needed when an
+                // earlier position had exactly that many. Capturing moves always get it
+                let earlier = self
+                    .history
+                    .iter()
+                    .filter(|c| c.action != Move::Pass)
+                    .map(|c| c.previous_stones)
+                    .collect::<HashSet<_>>();
+                let placed = match color {
+                    Tile::Black => (self.stones.0 + 1, self.stones.1),
+                    _ => (self.stones.0, self.stones.1 + 1),
+                };
+                let may_repeat = earlier.contains(&placed);
+
+                std::iter::once(Move::Pass).chain(self.placements(color).filter(move |&mv| {
+                    let checked = may_repeat || self.captures_at(mv, color);
+                    !checked || !self.would_repeat_as(color, mv)
+                }))
+            })
+            .into_iter()
+            .flatten()
+    }
+
+    // Whether each point is a legal placement for the side to move, all false once the game is over
+    pub fn legal_map(&self) -> Vec<bool> {
+        let mut legal = vec![false; self.pos_to_chain.len()];
+        let color = self.turn.get_placing_color().unwrap_or(Tile::Free);
+        for mv in self.moves_for(color) {
+            if let Some(pos) = self.move_position(mv) {
+                legal[pos] = true;
+            }
+        }
+        legal
+    }
+
+    pub fn playable_area(&self) -> usize {
+        self.pos_to_chain.iter().filter(|c| c.is_some()).count()
+    }
+
+    // Share of the playable points holding a stone, a board without any counts as full
+    pub fn fill_ratio(&self) -> f32 {
+        match self.playable_area() {
+            0 => 1.0,
+            area => (self.stones.0 + self.stones.1) as f32 / area as f32,
+        }
+    }
+
+    // One side owning every playable point, with komi counting against Black or for White
+    pub fn max_score(&self) -> f32 {
+        self.playable_area() as f32 + self.komi.abs()
+    }
+
+    pub fn set_komi(&mut self, komi: f32) -> Result<(), BoardError> {
+        if !komi.is_finite() {
+            return Err(BoardError::InvalidKomi(komi));
+        }
+        self.komi = komi;
+        Ok(())
+    }
+
+    // The most recent move in coordinates, None before the first move
+    pub fn last_move(&self) -> Option<Move> {
+        self.history
+            .last()
+            .map(|change| self.to_coords_move(self.to_place_move(change.action)))
+    }
+
+    pub fn to_coords_move(&self, mv: Move) -> Move {
+        match mv {
+            Move::Place(p) => Move::Coords(self.to_coords(p)),
+            a => a,
+        }
+    }
+
+    pub fn to_place_move(&self, mv: Move) -> Move {
+        match mv {
+            Move::Coords((x, y)) => Move::Place(self.to_pos(x, y)),
+            a => a,
+        }
+    }
+
+    pub(crate) fn move_position(&self, mv: Move) -> Option<usize> {
+        match mv {
+            Move::Place(p) => Some(p),
+            Move::Coords((x, y)) => Some(self.to_pos(x, y)),
+            Move::Pass => None,
+        }
+    }
+
+    pub fn connected_components(&self) -> Vec<Vec<usize>> {
+        let mut seen = vec![false; self.pos_to_chain.len()];
+        let mut components = Vec::new();
+
+        for start in 0..self.pos_to_chain.len() {
+            if seen[start] || self.get_tile(start) == Tile::Dead {
+                continue;
+            }
+
+            seen[start] = true;
+            let mut component = vec![start];
+            let mut queue = VecDeque::from([start]);
+            while let Some(pos) = queue.pop_front() {
+                for n in self.orthogonal_neighbors(pos) {
+                    if !seen[n] && self.get_tile(n) != Tile::Dead {
+                        seen[n] = true;
+                        component.push(n);
+                        queue.push_back(n);
+                    }
+                }
+            }
+
+            component.sort_unstable();
+            components.push(component);
+        }
+
+        components
+    }
+
+    // Black's area minus White's for each of the connected_components, dame and dead tiles count
+    // for neither side
+    pub fn region_scores(&self) -> Vec<f32> {
+        let ownership = self.ownership();
+        self.connected_components()
+            .iter()
+            .map(|component| {
+                component
+                    .iter()
+                    .map(|&p| match ownership[p] {
+                        Some(Tile::Black) => 1.0,
+                        Some(Tile::White) => -1.0,
+                        _ => 0.0,
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+
+    // Area score with komi, summed over the regions. Free regions never reach past dead tiles,
+    // so this always matches the score the heuristic uses
+    pub fn score(&self) -> f32 {
+        let score = self.region_scores().iter().sum::<f32>() - self.komi;
+        debug_assert_eq!(score, self.strict_score());
+        score
+    }
+
+    // Area score of a finished game, a drawn game has Turn::None as the winner
+    pub fn result(&self) -> Option<GameResult> {
+        if self.turn != Turn::None {
+            return None;
+        }
+
+        let score = self.strict_score();
+        let winner = if score > 0.0 {
+            Turn::Black
+        } else if score < 0.0 {
+            Turn::White
+        } else {
+            Turn::None
+        };
+        Some(GameResult {
+            winner,
+            margin: score.abs(),
+        })
+    }
+
+    pub fn liberty_summary(&self) -> LibertySummary {
+        let mut summary = LibertySummary::default();
+
+        for c in self.chains.iter().filter_map(|a| a.as_ref()) {
+            let entry = match c.tile {
+                Tile::Black => &mut summary.black,
+                Tile::White => &mut summary.white,
+                _ => continue,
+            };
+
+            entry.groups += 1;
+            entry.liberties += c.liberty_count();
+            if c.is_in_atari() {
+                entry.atari += 1;
+            }
+        }
+
+        summary
+    }
+
+    // Every group with a single liberty, together with its stones and that liberty
+    pub fn ataris(&self) -> Vec<(Tile, Vec<usize>, usize)> {
+        self.chains
+            .iter()
+            .filter_map(|a| a.as_ref())
+            .filter(|c| c.tile != Tile::Free && c.is_in_atari())
+            .map(|c| {
+                let mut stones = c.positions.iter().copied().collect::<Vec<_>>();
+                stones.sort_unstable();
+                (c.tile, stones, *c.liberties.iter().next().unwrap())
+            })
+            .collect()
+    }
+
+    // Every position whose tile differs, as (position, tile in other, tile in self)
+    pub fn diff(&self, other: &Board) -> Result<Vec<(usize, Tile, Tile)>, BoardError> {
+        if self.size != other.size {
+            return Err(BoardError::SizeMismatch(self.size, other.size));
+        }
+
+        Ok((0..self.pos_to_chain.len())
+            .map(|p| (p, other.get_tile(p), self.get_tile(p)))
+            .filter(|(_, old, new)| old != new)
+            .collect())
+    }
+
+    pub fn is_self_atari(&self, mv: Move) -> bool {
+        let pos = match self.move_position(mv) {
+            Some(p) => p,
+            None => return false,
+        };
+        let friendly_color = match self.turn.get_placing_color() {
+            Some(c) => c,
+            None => return false,
+        };
+        if pos >= self.pos_to_chain.len() || self.get_tile(pos) != Tile::Free {
+            return false;
+        }
+
+        let mut liberties: HashSet<usize> = HashSet::new();
+        for n in self.neighbors(pos) {
+            let (_, chain) = match self.get_chain(n) {
+                Some(c) => c,
+                None => continue,
+            };
+
+            if chain.tile == Tile::Free {
+                liberties.insert(n);
+            } else if chain.tile == friendly_color {
+                liberties.extend(chain.liberties.iter());
+            } else if chain.is_in_atari() {
+                return false;
+            }
+        }
+        liberties.remove(&pos);
+
+        liberties.len() == 1
+    }
+
+    // Liberties of the placed stone's merged chain, None if the move is illegal
+    pub fn liberties_after(&self, mv: Move) -> Option<usize> {
+        let pos = self.move_position(mv)?;
+        let friendly_color = self.turn.get_placing_color()?;
+        if pos >= self.pos_to_chain.len() || self.get_tile(pos) != Tile::Free {
+            return None;
+        }
+
+        let mut stones: HashSet<usize> = HashSet::from([pos]);
+        let mut liberties: HashSet<usize> = HashSet::new();
+        let mut captured: Vec<usize> = Vec::new();
+        for n in self.neighbors(pos) {
+            let (_, chain) = match self.get_chain(n) {
+                Some(c) => c,
+                None => continue,
+            };
+
+            if chain.tile == Tile::Free {
+                liberties.insert(n);
+            } else if chain.tile == friendly_color {
+                stones.extend(chain.positions.iter());
+                liberties.extend(chain.liberties.iter());
+            } else if chain.is_in_atari() {
+                captured.extend(chain.positions.iter());
+            }
         }
-        if x + 1 < self.size as usize {
-            nbrs.push(self.to_pos(x + 1, y));
+
+        // Captured stones only become liberties where they touch the merged chain
+        for p in captured {
+            if self.neighbors(p).iter().any(|n| stones.contains(n)) {
+                liberties.insert(p);
+            }
         }
-        if y > 0 {
-            nbrs.push(self.to_pos(x, y - 1));
+        liberties.remove(&pos);
+
+        if liberties.is_empty() || self.would_repeat(mv) {
+            return None;
         }
-        if y + 1 < self.size as usize {
-            nbrs.push(self.to_pos(x, y + 1));
+        Some(liberties.len())
+    }
+
+    // Stones the placement would take off the board, empty for a pass or an illegal move
+    pub fn captures_of(&self, mv: Move) -> Vec<usize> {
+        let (Some(pos), Some(friendly_color)) =
+            (self.move_position(mv), self.turn.get_placing_color())
+        else {
+            return Vec::new();
+        };
+        if self.liberties_after(mv).is_none() {
+            return Vec::new();
         }
-        nbrs
+
+        let mut captured = self
+            .neighbors(pos)
+            .into_iter()
+            .filter_map(|n| self.get_chain(n))
+            .filter(|(_, c)| c.tile != Tile::Free && c.tile != friendly_color && c.is_in_atari())
+            .flat_map(|(_, c)| c.positions.iter().copied())
+            .collect::<Vec<_>>();
+        captured.sort_unstable();
+        captured.dedup();
+        captured
+    }
+
+    fn is_eye(&self, pos: usize, tile: Tile) -> bool {
+        self.neighbors(pos)
+            .into_iter()
+            .filter(|&n| self.get_tile(n) != Tile::Dead)
+            .all(|n| self.get_tile(n) == tile)
     }
 
     pub fn compute_board_hash(&self) -> u64 {
@@ -185,6 +643,91 @@ impl Board {
         hasher.finish()
     }
 
+    // The positions the superko check compares against, regardless of the order they were
+    // reached in. Equal boards with equal digests have the same legal moves
+    pub fn history_digest(&self) -> u64 {
+        let mut hashes = self
+            .history
+            .iter()
+            .filter(|c| c.action != Move::Pass)
+            .map(|c| c.board_hash)
+            .collect::<Vec<_>>();
+        hashes.sort_unstable();
+        hashes.dedup();
+
+        let mut hasher = DefaultHasher::new();
+        hashes.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    pub fn would_repeat(&self, mv: Move) -> bool {
+        match self.turn.get_placing_color() {
+            Some(c) => self.would_repeat_as(c, mv),
+            None => false,
+        }
+    }
+
+    // Whether placing at mv takes an opponent chain's last liberty, without building anything
+    fn captures_at(&self, mv: Move, friendly_color: Tile) -> bool {
+        let Some(pos) = self.move_position(mv) else {
+            return false;
+        };
+        self.neighbors(pos)
+            .iter()
+            .any(|&n| match self.get_chain(n) {
+                Some((_, chain)) => {
+                    chain.tile != Tile::Free
+                        && chain.tile != friendly_color
+                        && chain.is_in_atari()
+                        && chain.liberties.contains(&pos)
+                }
+                None => false,
+            })
+    }
+
+    fn would_repeat_as(&self, friendly_color: Tile, mv: Move) -> bool {
+        let pos = match self.move_position(mv) {
+            Some(p) => p,
+            None => return false,
+        };
+        if pos >= self.pos_to_chain.len() || self.get_tile(pos) != Tile::Free {
+            return false;
+        }
+
+        let mut tiles = self.iter_tiles().map(|(_, t)| t).collect::<Vec<_>>();
+        tiles[pos] = friendly_color;
+        for n in self.neighbors(pos) {
+            let (_, chain) = match self.get_chain(n) {
+                Some(c) => c,
+                None => continue,
+            };
+
+            if chain.tile != Tile::Free
+                && chain.tile != friendly_color
+                && chain.is_in_atari()
+                && chain.liberties.contains(&pos)
+            {
+                for &p in chain.positions.iter() {
+                    tiles[p] = Tile::Free;
+                }
+            }
+        }
+
+        let mut hasher = DefaultHasher::new();
+        for t in tiles {
+            t.hash(&mut hasher);
+        }
+        let hash = hasher.finish();
+
+        self.history
+            .iter()
+            .any(|c| c.action != Move::Pass && c.board_hash == hash)
+    }
+
+    pub fn iter_tiles(&self) -> impl Iterator<Item = ((usize, usize), Tile)> + '_ {
+        (0..self.pos_to_chain.len()).map(|p| (self.to_coords(p), self.get_tile(p)))
+    }
+
     pub fn get_tile(&self, pos: usize) -> Tile {
         match self.pos_to_chain[pos] {
             None => Tile::Dead,
@@ -233,19 +776,73 @@ impl Board {
         }
     }
 
-    pub fn from_rep(rep: String, size: u8, starting_turn: Turn, komi: f32) -> Result<Self, String> {
-        if rep.len() != (size as usize).pow(2) {
-            return Err("Invalid shape".to_string());
+    // Black to move with DEFAULT_KOMI, the size is taken from the square root of the length
+    pub fn from_rep_default(rep: String) -> Result<Self, BoardError> {
+        let len = rep.chars().count();
+        let size = (1..=u8::MAX as usize)
+            .find(|s| s * s >= len)
+            .filter(|s| s * s == len)
+            .ok_or(BoardError::InvalidRep(format!(
+                "Invalid shape: {} characters is not a square board",
+                len
+            )))?;
+
+        Board::from_rep(rep, size as u8, Turn::Black, DEFAULT_KOMI)
+    }
+
+    // Checks the length and characters of a rep without building a board
+    pub fn reshape_check(rep: &str, size: u8) -> Result<(), BoardError> {
+        if size == 0 {
+            return Err(BoardError::InvalidRep(
+                "Invalid size: the board needs at least one tile".to_string(),
+            ));
+        }
+
+        let expected = (size as usize).pow(2);
+        let actual = rep.chars().count();
+        if actual != expected {
+            return Err(BoardError::InvalidRep(format!(
+                "Invalid shape: expected {} characters for size {}, got {}",
+                expected, size, actual
+            )));
+        }
+
+        match rep.chars().position(|t| Tile::from_char(t).is_none()) {
+            Some(p) => Err(BoardError::InvalidRep(format!(
+                "Invalid char '{}' at position {} (row {}, col {})",
+                rep.chars().nth(p).unwrap(),
+                p,
+                p / size as usize,
+                p % size as usize
+            ))),
+            None => Ok(()),
         }
+    }
+
+    // Rows of at most size characters, the last one is shorter if the rep doesn't fit
+    pub fn rep_to_rows(rep: &str, size: u8) -> Vec<String> {
+        rep.chars()
+            .collect::<Vec<_>>()
+            .chunks(size.max(1) as usize)
+            .map(|row| row.iter().collect::<String>())
+            .collect()
+    }
+
+    pub fn from_rep(
+        rep: String,
+        size: u8,
+        starting_turn: Turn,
+        komi: f32,
+    ) -> Result<Self, BoardError> {
+        Board::reshape_check(&rep, size)?;
 
         let mut board = Self::new(size, starting_turn, komi);
 
         let mut seen: HashSet<usize> = HashSet::new();
-        let mut rep_tiles: Vec<Tile> = Vec::with_capacity((size as usize).pow(2));
-        for t in rep.chars() {
-            let tile = Tile::from_char(t).ok_or_else(|| "Invalid char".to_string())?;
-            rep_tiles.push(tile);
-        }
+        let rep_tiles = rep
+            .chars()
+            .map(|t| Tile::from_char(t).unwrap())
+            .collect::<Vec<_>>();
 
         for (p, c) in rep_tiles.iter().enumerate() {
             if seen.contains(&p) {
@@ -266,18 +863,126 @@ impl Board {
 
             board.chains.push(Some(new_chain))
         }
+        board.stones = board.count_stones();
+        board.territory = board.count_territory();
+
+        Ok(board)
+    }
+
+    // True when passing is the only legal move, while the game isn't over yet
+    pub fn must_pass(&self) -> bool {
+        self.turn != Turn::None && self.moves().all(|m| m == Move::Pass)
+    }
+
+    // Like from_rep, but a group without liberties can't come from a real game
+    pub fn from_rep_strict(
+        rep: String,
+        size: u8,
+        starting_turn: Turn,
+        komi: f32,
+    ) -> Result<Self, BoardError> {
+        let board = Board::from_rep(rep, size, starting_turn, komi)?;
+
+        let captured = board
+            .chains
+            .iter()
+            .filter_map(|c| c.as_ref())
+            .filter(|c| c.tile != Tile::Free && c.liberties.is_empty())
+            .map(|c| (c.tile, *c.positions.iter().min().unwrap()))
+            .min_by_key(|(_, p)| *p);
+        if let Some((tile, pos)) = captured {
+            let (row, col) = board.to_coords(pos);
+            return Err(BoardError::InvalidRep(format!(
+                "Illegal position: the {:?} group at (row {}, col {}) has no liberties",
+                tile, row, col
+            )));
+        }
 
         Ok(board)
     }
 
     pub fn get_rep(&self) -> String {
-        (0..(self.size as usize).pow(2))
-            .map(|p| self.get_tile(p).to_char())
-            .collect()
+        self.iter_tiles().map(|(_, t)| t.to_char()).collect()
+    }
+
+    pub fn render_grid(&self) -> String {
+        Board::rep_to_rows(&self.get_rep(), self.size).join("\n")
+    }
+
+    // Header: size, turn, komi in hundredths (i32 LE), followed by 2 bits per tile
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let total = (self.size as usize).pow(2);
+        let mut bytes = Vec::with_capacity(BYTES_HEADER + total.div_ceil(4));
+
+        bytes.push(self.size);
+        bytes.push(match self.turn {
+            Turn::Black => 0,
+            Turn::White => 1,
+            Turn::None => 2,
+        });
+        bytes.extend(((self.komi * 100.0).round() as i32).to_le_bytes());
+
+        for p in 0..total {
+            let bits = match self.get_tile(p) {
+                Tile::Free => 0,
+                Tile::Black => 1,
+                Tile::White => 2,
+                Tile::Dead => 3,
+            };
+            if p % 4 == 0 {
+                bytes.push(0);
+            }
+            *bytes.last_mut().unwrap() |= bits << ((p % 4) * 2);
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BoardError> {
+        if bytes.len() < BYTES_HEADER {
+            return Err(BoardError::InvalidBytes(format!(
+                "Invalid header: expected {} bytes, got {}",
+                BYTES_HEADER,
+                bytes.len()
+            )));
+        }
+
+        let size = bytes[0];
+        let turn = match bytes[1] {
+            0 => Turn::Black,
+            1 => Turn::White,
+            2 => Turn::None,
+            t => return Err(BoardError::InvalidBytes(format!("Invalid turn byte {}", t))),
+        };
+        let komi = i32::from_le_bytes(bytes[2..BYTES_HEADER].try_into().unwrap()) as f32 / 100.0;
+
+        let total = (size as usize).pow(2);
+        let tiles = &bytes[BYTES_HEADER..];
+        if tiles.len() != total.div_ceil(4) {
+            return Err(BoardError::InvalidBytes(format!(
+                "Invalid shape: expected {} tile bytes, got {}",
+                total.div_ceil(4),
+                tiles.len()
+            )));
+        }
+
+        let rep = (0..total)
+            .map(|p| match (tiles[p / 4] >> ((p % 4) * 2)) & 0b11 {
+                0 => Tile::Free,
+                1 => Tile::Black,
+                2 => Tile::White,
+                _ => Tile::Dead,
+            })
+            .map(|t| t.to_char())
+            .collect::<String>();
+
+        Board::from_rep(rep, size, turn, komi)
     }
 
     fn rollback_change(&mut self, change: MoveChange) {
         self.turn = change.previous_turn;
+        self.stones = change.previous_stones;
+        self.territory = change.previous_territory;
 
         for m in change.mods.into_iter().rev() {
             match m {
@@ -304,26 +1009,43 @@ impl Board {
         None
     }
 
-    pub fn apply_move(&mut self, mut action: Move) -> Result<(), String> {
+    pub fn apply_move_as(&mut self, color: Tile, action: Move) -> Result<(), BoardError> {
+        if self.turn != Turn::None && self.turn.get_placing_color() != Some(color) {
+            return Err(BoardError::WrongTurn);
+        }
+        self.apply_move(action)
+    }
+
+    pub fn apply_move(&mut self, mut action: Move) -> Result<(), BoardError> {
         if self.turn == Turn::None {
-            return Err(format!("Game is over ({:?})", action));
+            return Err(BoardError::GameOver(action));
         }
 
         let mut change = MoveChange {
             action,
             previous_turn: self.turn,
+            previous_stones: self.stones,
+            previous_territory: self.territory,
             board_hash: self.compute_board_hash(),
             mods: Vec::new(),
         };
 
-        if let Move::Coords((x, y)) = action {
-            action = Move::Place(self.to_pos(x, y));
-        }
+        action = self.to_place_move(action);
 
         if let Move::Place(pos) = action {
+            let in_bounds = match change.action {
+                Move::Coords((x, y)) => x < self.size as usize && y < self.size as usize,
+                _ => pos < self.pos_to_chain.len(),
+            };
+            if !in_bounds {
+                return Err(BoardError::OutOfBounds(change.action));
+            }
             if self.get_tile(pos) != Tile::Free {
-                return Err(format!("Tile is occupied ({:?})", action));
+                return Err(BoardError::Occupied(action));
             }
+            // Only the region the stone lands in can change owner, captured chains had no free
+            // neighbors so every other region keeps its border
+            let region = self.region_territory(&[pos]);
 
             let neighbors = self
                 .neighbors(pos)
@@ -348,12 +1070,17 @@ impl Board {
                 change.mods.push(Mod::Change((chain_id, chain.clone())));
 
                 chain.liberties.remove(&pos);
-                if chain.liberties.len() > 0 {
+                if !chain.liberties.is_empty() {
                     continue;
                 }
 
                 chain.tile = Tile::Free;
+                let captured = chain.size();
                 let adjacents = chain.adjacent.iter().copied().collect::<Vec<_>>();
+                match opponent_color {
+                    Tile::Black => self.stones.0 -= captured,
+                    _ => self.stones.1 -= captured,
+                }
                 for adj in adjacents {
                     if self.pos_to_chain[adj].is_none() {
                         continue;
@@ -374,13 +1101,6 @@ impl Board {
                         continue;
                     }
 
-                    debug_assert!(
-                        adj_chain.tile != Tile::Free,
-                        "No liberties but adj chain is free ({:?} @{:?})",
-                        action,
-                        adj
-                    );
-
                     change.mods.push(Mod::Change((id, adj_chain.clone())));
                     adj_chain.liberties.extend(free_neighbors.iter());
                 }
@@ -406,6 +1126,10 @@ impl Board {
 
             let pos_id = self.pos_to_chain[pos].unwrap();
             change.mods.push(Mod::Assignment((pos, pos_id)));
+            match friendly_color {
+                Tile::Black => self.stones.0 += 1,
+                _ => self.stones.1 += 1,
+            }
 
             match friendly_chains.iter().collect::<Vec<_>>().as_slice() {
                 [] => {
@@ -539,10 +1263,24 @@ impl Board {
             } else {
                 self.chains[pos_id] = None;
             }
+
+            // Captures have already freed their liberties, so the merged chain decides legality
+            let (_, placed) = self.get_chain(pos).unwrap();
+            if placed.liberties.is_empty() {
+                self.rollback_change(change);
+                return Err(BoardError::Suicide(action));
+            }
+
+            // The pieces of the split region and the freed captures all touch the placed stone
+            let affected = self.region_territory(&self.neighbors(pos));
+            self.territory = (
+                self.territory.0 - region.0 + affected.0,
+                self.territory.1 - region.1 + affected.1,
+            );
         }
 
         if action == Move::Pass
-            && self.history.len() > 0
+            && !self.history.is_empty()
             && self.history.iter().last().unwrap().action == Move::Pass
         {
             self.turn = Turn::None;
@@ -551,121 +1289,1573 @@ impl Board {
         }
 
         let hash = self.compute_board_hash();
-        if self.history.len() > 0
+        if !self.history.is_empty()
             && self
                 .history
                 .iter()
                 .any(|c| c.action != Move::Pass && c.board_hash == hash)
         {
             self.rollback_change(change);
-            return Err("Repetition".to_string());
+            return Err(BoardError::Repetition);
         }
         self.history.push(change);
 
         Ok(())
     }
 
-    pub fn undo_move(&mut self) -> Result<(), String> {
-        if let Some(change) = self.history.pop() {
-            self.rollback_change(change);
-            Ok(())
-        } else {
-            Err("No move to undo".to_string())
-        }
+    // Walking the positions in order yields placements lazily and keeps the move order the same
+    // across runs, the chains themselves are hash sets
+    fn placements(&self, friendly_color: Tile) -> impl Iterator<Item = Move> + '_ {
+        (0..self.pos_to_chain.len())
+            .filter(move |&pos| self.can_place(pos, friendly_color))
+            .map(Move::Place)
     }
-}
-
-impl Heuristic for Board {
-    type Action = Move;
 
-    fn calculate_heuristic(&self) -> f32 {
-        let mut score = -self.komi;
+    fn can_place(&self, pos: usize, friendly_color: Tile) -> bool {
+        let Some((_, chain)) = self.get_chain(pos) else {
+            return false;
+        };
+        if chain.tile != Tile::Free {
+            return false;
+        }
+        if chain.size() >= 2 {
+            return true;
+        }
 
-        for c in self.chains.iter().filter_map(|a| a.as_ref()) {
-            if c.tile == Tile::Free {
-                let tile = c.adjacent.iter().find_map(|&a| match self.get_tile(a) {
-                    Tile::Dead => None,
-                    Tile::Free => None,
-                    a => Some(a),
-                });
-                if tile.is_some()
-                    && c.adjacent.iter().all(|&a| {
-                        let t = self.get_tile(a);
-                        t == Tile::Dead || t == tile.unwrap()
-                    })
-                {
-                    match tile.unwrap() {
-                        Tile::Black => score += c.positions.len() as f32,
-                        Tile::White => score -= c.positions.len() as f32,
-                        _ => panic!("not possible"),
-                    }
+        self.neighbors(pos)
+            .iter()
+            .filter(|&&n| self.pos_to_chain[n].is_some())
+            .any(|&n| {
+                let (_, n_chain) = self.get_chain(n).unwrap();
+                if n_chain.tile == friendly_color && n_chain.liberty_count() >= 2 {
+                    return true;
                 }
-                continue;
-            }
+                n_chain.tile != friendly_color
+                    && n_chain.is_in_atari()
+                    && n_chain.liberties.contains(&pos)
+            })
+    }
+
+    pub fn compact_chains(&mut self) {
+        if self.chains.iter().all(|c| c.is_some()) {
+            return;
+        }
 
-            match c.tile {
-                Tile::Black => score += c.positions.len() as f32,
-                Tile::White => score -= c.positions.len() as f32,
-                _ => panic!("not possible"),
+        let chain_count = self.chains.len();
+        let mut kept: Vec<usize> = Vec::new();
+        let mut remap: Vec<Option<usize>> = vec![None; chain_count];
+        let mut chains: Vec<Option<Chain>> = Vec::new();
+
+        for (old_id, chain) in self.chains.drain(..).enumerate() {
+            if let Some(mut chain) = chain {
+                let new_id = chains.len();
+                chain.id = new_id;
+                remap[old_id] = Some(new_id);
+                kept.push(old_id);
+                chains.push(Some(chain));
             }
         }
 
-        score
-    }
+        for id in self.pos_to_chain.iter_mut().flatten() {
+            *id = remap[*id].unwrap();
+        }
+        self.chains = chains;
 
-    fn is_terminal(&self) -> bool {
-        self.turn == Turn::None
+        self.compactions.push(Compaction {
+            history_len: self.history.len(),
+            chain_count,
+            kept,
+        });
     }
 
-    fn is_maximizing(&self) -> bool {
-        self.turn == Turn::Black
-    }
+    fn expand_chains(&mut self, compaction: Compaction) {
+        debug_assert_eq!(self.chains.len(), compaction.kept.len());
 
-    fn get_hash(&self) -> u64 {
-        self.compute_board_hash()
+        let mut chains: Vec<Option<Chain>> = vec![None; compaction.chain_count];
+        for (new_id, chain) in self.chains.drain(..).enumerate() {
+            let old_id = compaction.kept[new_id];
+            chains[old_id] = chain.map(|mut c| {
+                c.id = old_id;
+                c
+            });
+        }
+
+        for id in self.pos_to_chain.iter_mut().flatten() {
+            *id = compaction.kept[*id];
+        }
+        self.chains = chains;
     }
 
-    fn moves(&self) -> impl Iterator<Item = Self::Action> {
-        let mut possible_moves = vec![Move::Pass];
+    fn count_stones(&self) -> (usize, usize) {
+        self.iter_tiles().fold((0, 0), |(b, w), (_, t)| match t {
+            Tile::Black => (b + 1, w),
+            Tile::White => (b, w + 1),
+            _ => (b, w),
+        })
+    }
 
-        let friendly_color = self.turn.get_placing_color().unwrap();
+    // The color owning a free chain, a region bordering both colors or only dead tiles has none
+    fn region_owner(&self, chain: &Chain) -> Option<Tile> {
+        let tile = chain
+            .adjacent
+            .iter()
+            .find_map(|&a| match self.get_tile(a) {
+                Tile::Dead => None,
+                Tile::Free => None,
+                a => Some(a),
+            })?;
+        chain
+            .adjacent
+            .iter()
+            .all(|&a| {
+                let t = self.get_tile(a);
+                t == Tile::Dead || t == tile
+            })
+            .then_some(tile)
+    }
 
-        for chain in self.chains.iter().filter_map(|a| a.as_ref()) {
-            if chain.tile != Tile::Free {
+    // Territory of the distinct free chains covering the given positions
+    fn region_territory(&self, positions: &[usize]) -> (usize, usize) {
+        let mut seen: HashSet<usize> = HashSet::new();
+        let mut territory = (0, 0);
+        for &p in positions {
+            let Some((id, chain)) = self.get_chain(p) else {
                 continue;
-            }
-            if chain.positions.len() >= 2 {
-                possible_moves.extend(chain.positions.iter().map(|&p| Move::Place(p)));
+            };
+            if chain.tile != Tile::Free || !seen.insert(id) {
                 continue;
             }
+            match self.region_owner(chain) {
+                Some(Tile::Black) => territory.0 += chain.size(),
+                Some(Tile::White) => territory.1 += chain.size(),
+                _ => {}
+            }
+        }
+        territory
+    }
 
-            let &pos = chain.positions.iter().nth(0).unwrap();
-            let can_place = self
-                .neighbors(pos)
-                .iter()
-                .filter(|&&n| self.pos_to_chain[n].is_some())
-                .any(|&n| {
-                    let (_, n_chain) = self.get_chain(n).unwrap();
-                    if n_chain.tile == friendly_color && n_chain.liberties.len() >= 2 {
-                        return true;
-                    }
-                    n_chain.tile != friendly_color
-                        && n_chain.liberties.len() == 1
-                        && n_chain.liberties.contains(&pos)
-                });
-            if can_place {
-                possible_moves.push(Move::Place(pos));
+    fn count_territory(&self) -> (usize, usize) {
+        self.ownership()
+            .iter()
+            .zip(self.iter_tiles())
+            .fold((0, 0), |(b, w), (o, (_, t))| match (o, t) {
+                (Some(Tile::Black), Tile::Free) => (b + 1, w),
+                (Some(Tile::White), Tile::Free) => (b, w + 1),
+                _ => (b, w),
+            })
+    }
+
+    // Area ownership of every position, stones belong to their color and free tiles to the color
+    // that surrounds their region on its own
+    pub fn ownership(&self) -> Vec<Option<Tile>> {
+        let mut ownership = vec![None; self.pos_to_chain.len()];
+        for c in self.chains.iter().filter_map(|a| a.as_ref()) {
+            let owner = match c.tile {
+                Tile::Free => self.region_owner(c),
+                Tile::Dead => None,
+                t => Some(t),
+            };
+            for &p in c.positions.iter() {
+                ownership[p] = owner;
+            }
+        }
+        ownership
+    }
+
+    // Stones and territory are both counted incrementally
+    fn strict_score(&self) -> f32 {
+        debug_assert_eq!(self.stones, self.count_stones());
+        debug_assert_eq!(self.territory, self.count_territory());
+        -self.komi + (self.stones.0 + self.territory.0) as f32
+            - (self.stones.1 + self.territory.1) as f32
+    }
+
+    fn liberty_balance(&self) -> f32 {
+        let summary = self.liberty_summary();
+        summary.black.liberties as f32 - summary.white.liberties as f32
+    }
+
+    fn influence_score(&self) -> f32 {
+        let total = self.pos_to_chain.len();
+        let mut distance: Vec<usize> = vec![usize::MAX; total];
+        let mut owner: Vec<Option<Tile>> = vec![None; total];
+        let mut queue: VecDeque<usize> = VecDeque::new();
+
+        for p in 0..total {
+            let t = self.get_tile(p);
+            if t == Tile::Black || t == Tile::White {
+                distance[p] = 0;
+                owner[p] = Some(t);
+                queue.push_back(p);
+            }
+        }
+
+        while let Some(cur) = queue.pop_front() {
+            for n in self.neighbors(cur) {
+                if self.get_tile(n) != Tile::Free {
+                    continue;
+                }
+                if distance[n] == usize::MAX {
+                    distance[n] = distance[cur] + 1;
+                    owner[n] = owner[cur];
+                    queue.push_back(n);
+                } else if distance[n] == distance[cur] + 1 && owner[n] != owner[cur] {
+                    owner[n] = None;
+                }
+            }
+        }
+
+        let mut score = -self.komi;
+        for o in owner {
+            match o {
+                Some(Tile::Black) => score += 1.0,
+                Some(Tile::White) => score -= 1.0,
+                _ => {}
+            }
+        }
+        score
+    }
+
+    pub fn snapshot(&self) -> BoardSnapshot {
+        BoardSnapshot {
+            history_len: self.history.len(),
+            board_hash: self.compute_board_hash(),
+            turn: self.turn,
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: BoardSnapshot) -> Result<(), BoardError> {
+        if self.history.len() < snapshot.history_len {
+            return Err(BoardError::SnapshotAhead);
+        }
+
+        while self.history.len() > snapshot.history_len {
+            self.undo_move()?;
+        }
+
+        if self.turn != snapshot.turn || self.compute_board_hash() != snapshot.board_hash {
+            return Err(BoardError::SnapshotDiverged);
+        }
+        Ok(())
+    }
+
+    // The position before the first move, with the moves that were played from it
+    pub(crate) fn unwind(&self) -> Result<(Board, Vec<Move>), BoardError> {
+        let mut board = self.clone();
+        let mut moves = Vec::with_capacity(board.history.len());
+        while let Some(change) = board.history.last() {
+            moves.push(change.action);
+            board.undo_move()?;
+        }
+        moves.reverse();
+        Ok((board, moves))
+    }
+
+    pub fn undo_move(&mut self) -> Result<(), BoardError> {
+        if let Some(change) = self.history.pop() {
+            while self
+                .compactions
+                .last()
+                .is_some_and(|c| c.history_len > self.history.len())
+            {
+                let compaction = self.compactions.pop().unwrap();
+                self.expand_chains(compaction);
+            }
+
+            self.rollback_change(change);
+            Ok(())
+        } else {
+            Err(BoardError::NoMoveToUndo)
+        }
+    }
+}
+
+impl Heuristic for Board {
+    type Action = Move;
+    type Error = BoardError;
+
+    fn calculate_heuristic(&self) -> f32 {
+        let sign = if self.maximizing == Tile::White {
+            -1.0
+        } else {
+            1.0
+        };
+        sign * match self.heuristic_mode {
+            HeuristicMode::AreaStrict => self.strict_score(),
+            HeuristicMode::AreaLiberties => {
+                self.strict_score() + LIBERTY_WEIGHT * self.liberty_balance()
             }
+            HeuristicMode::Influence => self.influence_score(),
         }
+    }
 
+    // Kept above zero, since playouts divide by it
+    fn max_score(&self) -> f32 {
+        Board::max_score(self).max(1.0)
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.turn == Turn::None
+    }
+
+    fn is_maximizing(&self) -> bool {
+        self.turn.get_placing_color() == Some(self.maximizing)
+    }
+
+    fn passed(&self) -> bool {
+        self.history.last().is_some_and(|c| c.action == Move::Pass)
+    }
+
+    fn fill_ratio(&self) -> f32 {
+        Board::fill_ratio(self)
+    }
+
+    fn open_points(&self) -> f32 {
+        (self.playable_area() - self.stones.0 - self.stones.1) as f32
+    }
+
+    fn move_number(&self) -> usize {
+        self.history.len()
+    }
+
+    fn line_of(&self, mv: Move) -> Option<usize> {
+        let pos = self.move_position(mv)?;
+        let (row, col) = self.to_coords(pos);
+        let last = self.size as usize - 1;
+        Some(row.min(col).min(last - row).min(last - col) + 1)
+    }
+
+    // Search caches may be shared between boards, so everything that affects a score is keyed
+    fn get_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        self.turn.hash(&mut hasher);
+        self.komi.to_bits().hash(&mut hasher);
+        self.heuristic_mode.hash(&mut hasher);
+        self.maximizing.hash(&mut hasher);
+        self.passed().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn moves(&self) -> impl Iterator<Item = Self::Action> {
+        // Nobody can move once the game is over, undoing the final move brings them back
+        self.turn
+            .get_placing_color()
+            .into_iter()
+            .flat_map(|c| self.moves_for(c))
+    }
+
+    // A finished game has nothing left to play, so it's decided and has no moves
+    fn is_decided(&self) -> bool {
+        let (Some(friendly_color), Some(opponent_color)) = (
+            self.turn.get_placing_color(),
+            self.turn.next().get_placing_color(),
+        ) else {
+            return true;
+        };
+
+        self.placements(friendly_color).next().is_none()
+            && self.placements(opponent_color).next().is_none()
+    }
+
+    fn playout_moves(&self) -> impl Iterator<Item = Self::Action> {
+        let Some(friendly_color) = self.turn.get_placing_color() else {
+            return Vec::new().into_iter();
+        };
+
+        let mut possible_moves = self
+            .moves()
+            .filter(|&mv| match mv {
+                Move::Place(pos) => !self.is_eye(pos, friendly_color) && !self.is_self_atari(mv),
+                _ => false,
+            })
+            .collect::<Vec<_>>();
+        if possible_moves.is_empty() {
+            possible_moves.push(Move::Pass);
+        }
+
+        possible_moves.into_iter()
+    }
+
+    fn ordered_moves(&self) -> impl Iterator<Item = Self::Action> {
+        let Some(friendly_color) = self.turn.get_placing_color() else {
+            return Vec::new().into_iter();
+        };
+
+        // Captures, then atari escapes, then contact moves, then the rest, with Pass last
+        let rank = |mv: &Move| {
+            let pos = match self.move_position(*mv) {
+                Some(p) => p,
+                None => return 4,
+            };
+            let mut rank = 3;
+            for n in self.neighbors(pos) {
+                let (_, chain) = match self.get_chain(n) {
+                    Some(c) => c,
+                    None => continue,
+                };
+                if chain.tile == Tile::Free {
+                    continue;
+                }
+
+                let atari = chain.is_in_atari();
+                rank = rank.min(match (chain.tile == friendly_color, atari) {
+                    (false, true) => 0,
+                    (true, true) => 1,
+                    _ => 2,
+                });
+            }
+            rank
+        };
+
+        let mut possible_moves = self.moves().collect::<Vec<_>>();
+        possible_moves.sort_by_key(rank);
         possible_moves.into_iter()
     }
 
-    fn play(&mut self, mv: Self::Action) -> Result<(), String> {
+    fn play(&mut self, mv: Self::Action) -> Result<(), BoardError> {
         self.apply_move(mv)
     }
 
-    fn undo(&mut self) -> Result<(), String> {
+    fn undo(&mut self) -> Result<(), BoardError> {
         self.undo_move()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use evaluation::{
+        alphabeta::{AlphaBeta, AlphaBetaSession, CacheOption},
+        montecarlo::MonteCarloSession,
+        pick_best, AnyEvaluationSession, EvaluationSession, Evaluator, Heuristic,
+    };
+
+    use super::*;
+
+    fn board(rows: &[&str], turn: Turn) -> Board {
+        Board::from_rep(rows.concat(), rows.len() as u8, turn, 0.5).unwrap()
+    }
+
+    fn empty(size: u8) -> Board {
+        board(
+            &vec![".".repeat(size as usize).as_str(); size as usize],
+            Turn::Black,
+        )
+    }
+
+    // Plays the first evaluated move until the game ends, bounded so a loop fails the test
+    fn play_out(mut board: Board) -> Board {
+        let evaluator = AlphaBeta::new(2, CacheOption::Disable);
+        for _ in 0..100 {
+            if board.turn == Turn::None {
+                return board;
+            }
+            let moves = evaluator.evaluate(&mut board).unwrap();
+            board.apply_move(moves[0].0).unwrap();
+        }
+        panic!("the game didn't end");
+    }
+
+    // Fixed xorshift sequence standing in for the random picks of a playout
+    fn pick(seed: &mut u64, len: usize) -> usize {
+        *seed ^= *seed << 13;
+        *seed ^= *seed >> 7;
+        *seed ^= *seed << 17;
+        *seed as usize % len
+    }
+
+    // Random rollout in the style of the Monte Carlo playouts, returns the number of moves
+    // played before the first pass
+    fn moves_before_pass(mut board: Board, suppress_pass: bool, mut seed: u64) -> usize {
+        let mut played = 0;
+        while board.last_move() != Some(Move::Pass) && played < 1000 {
+            let candidates = match suppress_pass {
+                true => board.playout_moves().collect::<Vec<_>>(),
+                false => board.moves().collect::<Vec<_>>(),
+            };
+            board
+                .apply_move(candidates[pick(&mut seed, candidates.len())])
+                .unwrap();
+            played += 1;
+        }
+        played
+    }
+
+    #[test]
+    fn suppressing_pass_keeps_rollouts_going_on_an_open_board() {
+        let seeds = (1..=20).map(|s| s * 7919);
+        let (early, suppressed) = seeds.fold((0, 0), |(early, suppressed), seed| {
+            (
+                early + moves_before_pass(empty(5), false, seed),
+                suppressed + moves_before_pass(empty(5), true, seed),
+            )
+        });
+        assert!(early < suppressed, "{} vs {} moves", early, suppressed);
+
+        // The root keeps its pass either way
+        assert!(empty(5).moves().any(|m| m == Move::Pass));
+        assert!(!empty(5).playout_moves().any(|m| m == Move::Pass));
+    }
+
+    #[test]
+    fn filled_boards_score_without_searching() {
+        let mut filled = board(&["XXXXX", "XXXXX", "XXXXX", "OOOOO", "OOOOO"], Turn::Black);
+        assert!(filled.is_decided());
+
+        let started = std::time::Instant::now();
+        let moves = AlphaBeta::new(12, CacheOption::Disable)
+            .evaluate(&mut filled)
+            .unwrap();
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+        assert_eq!(moves, vec![(Move::Pass, 15.0 - 10.0 - 0.5)]);
+        assert_eq!(filled.calculate_heuristic(), 4.5);
+    }
+
+    #[test]
+    fn compaction_bounds_the_chains_across_captures() {
+        let (mut board, mut seed, mut captures) = (empty(5), 11, 0);
+        let mut played = 0;
+        while board.turn != Turn::None && played < 500 {
+            let free = board.get_rep().matches('.').count();
+            // Passing only when nothing else is legal keeps the stones coming off the board
+            let mut candidates = board.moves().collect::<Vec<_>>();
+            if candidates.len() > 1 {
+                candidates.retain(|&m| m != Move::Pass);
+            }
+            board
+                .apply_move(candidates[pick(&mut seed, candidates.len())])
+                .unwrap();
+            played += 1;
+            if board.get_rep().matches('.').count() > free {
+                captures += 1;
+            }
+
+            let rep = board.get_rep();
+            board.compact_chains();
+            assert_eq!(board.get_rep(), rep);
+            assert!(board.chains.len() <= 25, "{} chains", board.chains.len());
+        }
+        assert!(captures > 5, "only {} captures", captures);
+
+        // Undo reaches back through every compaction
+        while board.undo_move().is_ok() {}
+        assert_eq!(board.get_rep(), empty(5).get_rep());
+    }
+
+    #[test]
+    fn self_atari_leaves_a_single_liberty() {
+        let board = board(&["..O..", ".O...", ".....", ".....", "....."], Turn::Black);
+        assert!(board.is_self_atari(Move::Coords((0, 1))));
+        assert!(!board.is_self_atari(Move::Coords((3, 3))));
+        assert!(!board.is_self_atari(Move::Pass));
+    }
+
+    #[test]
+    fn liberty_summary_counts_only_stones() {
+        let board = board(&["XX.O.", "O.#..", "XO...", ".....", "....."], Turn::Black);
+        let summary = board.liberty_summary();
+        assert_eq!(
+            summary.black,
+            GroupLiberties {
+                groups: 2,
+                atari: 1,
+                liberties: 3,
+            }
+        );
+        assert_eq!(
+            summary.white,
+            GroupLiberties {
+                groups: 3,
+                atari: 1,
+                liberties: 7,
+            }
+        );
+    }
+
+    #[test]
+    fn filled_boards_stop_after_one_pass() {
+        let mut filled = board(&["XXXXX", "XXXXX", "XXXXX", "OOOOO", "OOOOO"], Turn::Black);
+        let results = AlphaBeta::new(12, CacheOption::Disable)
+            .evaluate_detailed(&mut filled)
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!((results[0].mv, results[0].depth), (Move::Pass, 1));
+
+        let scores = evaluation::montecarlo::MonteCarlo::new(std::time::Duration::from_millis(20))
+            .evaluate(&mut filled)
+            .unwrap();
+        assert_eq!(
+            scores.iter().map(|s| s.0).collect::<Vec<_>>(),
+            vec![Move::Pass]
+        );
+        assert!(filled.history.is_empty());
+    }
+
+    #[test]
+    fn ending_the_game_reports_a_shallow_depth() {
+        // White passed, so Black's pass ends the game right away
+        let mut board = empty(3).with_turn(Turn::White).unwrap();
+        board.apply_move(Move::Pass).unwrap();
+
+        let results = AlphaBeta::new(3, CacheOption::Disable)
+            .evaluate_detailed(&mut board)
+            .unwrap();
+        let depth_of = |mv| results.iter().find(|e| e.mv == mv).unwrap().depth;
+        assert_eq!(depth_of(Move::Pass), 1);
+        assert!(depth_of(Move::Place(4)) >= 3);
+    }
+
+    #[test]
+    fn white_leads_are_negative_for_either_side_to_move() {
+        let rows = ["OOOOO", "O...O", "O.X.O", "O...O", "OOOOO"];
+        for turn in [Turn::Black, Turn::White] {
+            let mut board = board(&rows, turn);
+            let score = board.calculate_heuristic();
+            assert!(score < 0.0, "{:?} to move scored {}", turn, score);
+            assert_eq!(board.for_side_to_move(score) > 0.0, turn == Turn::White);
+
+            let moves = AlphaBeta::new(2, CacheOption::Disable)
+                .evaluate(&mut board)
+                .unwrap();
+            assert!(moves.iter().all(|(_, v)| *v < 0.0), "{:?}", moves);
+        }
+    }
+
+    #[test]
+    fn last_move_follows_play_and_undo() {
+        let mut board = empty(5);
+        assert_eq!(board.last_move(), None);
+
+        board.apply_move(Move::Place(7)).unwrap();
+        assert_eq!(board.last_move(), Some(Move::Coords((1, 2))));
+        board.apply_move(Move::Coords((3, 4))).unwrap();
+        assert_eq!(board.last_move(), Some(Move::Coords((3, 4))));
+        board.apply_move(Move::Pass).unwrap();
+        assert_eq!(board.last_move(), Some(Move::Pass));
+
+        board.undo_move().unwrap();
+        assert_eq!(board.last_move(), Some(Move::Coords((3, 4))));
+        board.undo_move().unwrap();
+        board.undo_move().unwrap();
+        assert_eq!(board.last_move(), None);
+    }
+
+    #[test]
+    fn must_pass_once_nothing_can_be_placed() {
+        // The board is full but for two Black eyes, suicide for White and fillable for Black
+        let full = board(&["XXXXX", "X.XXX", "XXXXX", "XXXX.", "XXXXX"], Turn::White);
+        assert!(full.must_pass());
+        assert!(!full.with_turn(Turn::Black).unwrap().must_pass());
+        assert!(!empty(5).must_pass());
+
+        let mut over = empty(3);
+        over.apply_move(Move::Pass).unwrap();
+        over.apply_move(Move::Pass).unwrap();
+        assert!(!over.must_pass());
+    }
+
+    #[test]
+    fn captures_of_previews_single_and_chain_captures() {
+        // The White corner stone falls at (1, 4), the White row at (2, 4)
+        let board = board(&["...XO", "XXXX.", "OOOO.", "XXXX.", "....."], Turn::Black);
+        assert_eq!(board.captures_of(Move::Place(9)), vec![4]);
+        assert_eq!(board.captures_of(Move::Place(14)), vec![10, 11, 12, 13]);
+        assert_eq!(board.captures_of(Move::Place(24)), Vec::<usize>::new());
+        assert_eq!(board.captures_of(Move::Pass), Vec::<usize>::new());
+
+        let mut after = board.clone();
+        after.apply_move(Move::Place(14)).unwrap();
+        assert!((10..14).all(|p| after.get_tile(p) == Tile::Free));
+    }
+
+    #[test]
+    fn strict_reps_reject_groups_without_liberties() {
+        // The White pair on the right edge is surrounded
+        let rep = ["..XOO", "...XX", ".....", ".....", "....."].concat();
+        assert!(Board::from_rep(rep.clone(), 5, Turn::Black, 0.5).is_ok());
+        assert_eq!(
+            Board::from_rep_strict(rep, 5, Turn::Black, 0.5).err(),
+            Some(BoardError::InvalidRep(
+                "Illegal position: the White group at (row 0, col 3) has no liberties".to_string()
+            ))
+        );
+
+        let rep = ["..XO.", "...XX", ".....", ".....", "....."].concat();
+        assert!(Board::from_rep_strict(rep, 5, Turn::Black, 0.5).is_ok());
+    }
+
+    #[test]
+    fn diff_shows_captured_stones_as_freed() {
+        let before = board(&["XXXX.", "OOOO.", "XXXX.", ".....", "....."], Turn::Black);
+        let mut after = before.clone();
+        after.apply_move(Move::Place(9)).unwrap();
+
+        let mut expected = (5..9)
+            .map(|p| (p, Tile::White, Tile::Free))
+            .collect::<Vec<_>>();
+        expected.push((9, Tile::Free, Tile::Black));
+        assert_eq!(after.diff(&before), Ok(expected));
+        assert_eq!(after.diff(&after), Ok(Vec::new()));
+        assert_eq!(after.diff(&empty(3)), Err(BoardError::SizeMismatch(5, 3)));
+    }
+
+    #[test]
+    fn only_the_candidates_are_evaluated() {
+        let candidates = [Move::Place(6), Move::Place(12)];
+        let only_candidates = |moves: Vec<(Move, f32)>| {
+            let mut moves = moves.into_iter().map(|(mv, _)| mv).collect::<Vec<_>>();
+            moves.sort_by_key(|mv| empty(5).move_position(*mv));
+            assert_eq!(moves, candidates);
+        };
+
+        let alpha_beta = AlphaBeta::new(2, CacheOption::Disable);
+        only_candidates(
+            alpha_beta
+                .evaluate_candidates(&mut empty(5), &candidates)
+                .unwrap(),
+        );
+        let monte_carlo =
+            evaluation::montecarlo::MonteCarlo::new(std::time::Duration::from_millis(20));
+        only_candidates(
+            monte_carlo
+                .evaluate_candidates(&mut empty(5), &candidates)
+                .unwrap(),
+        );
+    }
+
+    #[test]
+    fn time_budgets_hold_on_a_wide_board() {
+        let budget = std::time::Duration::from_millis(150);
+        let mut root = empty(9);
+        root.apply_move(Move::Coords((4, 4))).unwrap();
+        let search = AlphaBeta::new(8, CacheOption::Capacity(1 << 16)).with_time_budget(budget);
+
+        let start = std::time::Instant::now();
+        let moves = search.evaluate(&mut root).unwrap();
+        let elapsed = start.elapsed();
+        assert_eq!(moves.len(), root.branching_factor());
+        assert!(elapsed < budget * 2, "{:?} for {:?}", elapsed, budget);
+    }
+
+    #[test]
+    fn endgame_depth_searches_a_full_board_with_fewer_nodes() {
+        // Both groups are down to their last liberty, Black to move takes White's at (3, 4)
+        let rows = ["XXXXX", "X.XOO", "XXXOO", "OOOO.", "OOOOO"];
+        let search = |endgame: bool| {
+            let mut session =
+                AlphaBetaSession::new(board(&rows, Turn::Black), 4, CacheOption::Capacity(1 << 16));
+            if endgame {
+                session = session.with_endgame(0.9, 1);
+            }
+            let best = session.best_move().unwrap();
+            (best, session.table_stats().unwrap().stored)
+        };
+        assert!(board(&rows, Turn::Black).fill_ratio() > 0.9);
+
+        let ((full_move, _), full_nodes) = search(false);
+        let ((endgame_move, value), endgame_nodes) = search(true);
+        assert_eq!(full_move, Move::Place(19));
+        assert_eq!(endgame_move, full_move);
+        assert!(value > 0.0, "{}", value);
+        assert!(
+            endgame_nodes * 4 < full_nodes,
+            "{} vs {} stored states",
+            endgame_nodes,
+            full_nodes
+        );
+    }
+
+    #[test]
+    fn maximizing_for_white_negates_the_root_evaluations() {
+        let rows = ["X.O..", ".XO..", "..O..", ".X...", "....."];
+        let evaluate = |maximizing: Tile, cache: CacheOption| {
+            let mut board = board(&rows, Turn::Black);
+            board.maximizing = maximizing;
+            let mut moves = AlphaBeta::new(2, cache).evaluate(&mut board).unwrap();
+            moves.sort_by_key(|(mv, _)| board.move_position(*mv));
+            moves
+        };
+
+        for cache in [|| CacheOption::Disable, || CacheOption::Capacity(1 << 12)] {
+            let black = evaluate(Tile::Black, cache());
+            let white = evaluate(Tile::White, cache());
+            assert_eq!(black.len(), white.len());
+            for ((mv, b), (_, w)) in black.iter().zip(&white) {
+                assert_eq!(*b, -*w, "{:?}", mv);
+            }
+            assert!(black.iter().any(|(_, v)| *v != 0.0));
+        }
+
+        let mut board = board(&rows, Turn::Black);
+        board.maximizing = Tile::White;
+        assert!(!board.is_maximizing());
+    }
+
+    #[test]
+    fn incremental_counts_match_a_full_recount_through_a_playout() {
+        let mut board = board(&["#....", ".....", "..#..", ".....", "....#"], Turn::Black);
+        let matches_recount = |board: &Board| {
+            board.stones == board.count_stones() && board.territory == board.count_territory()
+        };
+
+        let mut seed = 11;
+        let (mut played, mut captures) = (0, 0);
+        while !board.is_terminal() && played < 300 {
+            let moves = board.playout_moves().collect::<Vec<_>>();
+            let mv = match moves.is_empty() {
+                true => Move::Pass,
+                false => moves[pick(&mut seed, moves.len())],
+            };
+            let before = board.stones.0 + board.stones.1;
+            board.apply_move(mv).unwrap();
+            played += 1;
+            captures += usize::from(board.stones.0 + board.stones.1 < before);
+            assert!(matches_recount(&board), "after {} moves", played);
+        }
+
+        while !board.history.is_empty() {
+            board.undo_move().unwrap();
+            assert!(
+                matches_recount(&board),
+                "undoing at {}",
+                board.history.len()
+            );
+        }
+        assert!(captures > 0, "no captures in {} moves", played);
+        assert_eq!(board.stones, (0, 0));
+    }
+
+    #[test]
+    fn incremental_territory_matches_a_rebuilt_board() {
+        let rows = [
+            ".......", "...#...", ".......", "##...##", ".......", "...#...", ".......",
+        ];
+        let mut owned = 0;
+        for mut seed in [3, 17, 29] {
+            let mut board = board(&rows, Turn::Black);
+            for step in 0..120 {
+                // Every fourth step takes a move back, so undone regions are checked as well
+                if step % 4 == 3 && !board.history.is_empty() {
+                    board.undo_move().unwrap();
+                } else if board.is_terminal() {
+                    break;
+                } else {
+                    let moves = board.playout_moves().collect::<Vec<_>>();
+                    let mv = match moves.is_empty() {
+                        true => Move::Pass,
+                        false => moves[pick(&mut seed, moves.len())],
+                    };
+                    board.apply_move(mv).unwrap();
+                }
+
+                let rebuilt = Board::from_rep(board.get_rep(), 7, Turn::Black, 0.5).unwrap();
+                assert_eq!(board.territory, rebuilt.count_territory(), "seed {}", seed);
+                assert_eq!(board.ownership(), rebuilt.ownership(), "seed {}", seed);
+                owned += board.territory.0 + board.territory.1;
+            }
+        }
+        assert!(owned > 0);
+    }
+
+    #[test]
+    fn moves_for_generates_the_other_colors_placements() {
+        // Black to move, but the corner inside the Black stones is suicide for White
+        let board = board(&[".X.", "X..", "..."], Turn::Black);
+        let white = board.moves_for(Tile::White).collect::<Vec<_>>();
+        let expected = std::iter::once(Move::Pass)
+            .chain([2, 4, 5, 6, 7, 8].map(Move::Place))
+            .collect::<Vec<_>>();
+        assert_eq!(white, expected);
+
+        assert!(board.moves().any(|mv| mv == Move::Place(0)));
+        assert_eq!(board.turn, Turn::Black);
+        assert_eq!(board.moves_for(Tile::Dead).count(), 0);
+    }
+
+    #[test]
+    fn liberties_after_merges_and_captures() {
+        let applied = |board: &Board, mv: Move| {
+            let mut after = board.clone();
+            after.apply_move(mv).unwrap();
+            after
+                .get_chain(after.move_position(mv).unwrap())
+                .unwrap()
+                .1
+                .liberties
+                .len()
+        };
+
+        // Joining the two Black stones keeps the liberties of both, less the point filled
+        let merge = board(&["X.X..", ".....", ".....", ".....", "....."], Turn::Black);
+        assert_eq!(merge.liberties_after(Move::Place(1)), Some(4));
+        assert_eq!(applied(&merge, Move::Place(1)), 4);
+
+        // Taking the White corner stone frees the point it stood on
+        let capture = board(&["OX...", ".....", ".....", ".....", "....."], Turn::Black);
+        assert_eq!(capture.liberties_after(Move::Place(5)), Some(3));
+        assert_eq!(applied(&capture, Move::Place(5)), 3);
+
+        assert_eq!(capture.liberties_after(Move::Place(1)), None);
+        assert_eq!(capture.liberties_after(Move::Pass), None);
+    }
+
+    #[test]
+    fn with_turn_hands_the_move_to_the_other_side() {
+        // The corner is Black's own eye, and suicide for White
+        let mut board = board(&[".X.", "X..", "..."], Turn::Black);
+        board.apply_move(Move::Place(8)).unwrap();
+        let corner = Move::Place(0);
+        assert!(!board.moves().any(|mv| mv == corner));
+
+        let black = board.with_turn(Turn::Black).unwrap();
+        assert_eq!(black.turn, Turn::Black);
+        assert_eq!(black.history.len(), board.history.len());
+        assert!(black.moves().any(|mv| mv == corner));
+        assert!(!black.moves().any(|mv| mv == Move::Place(8)));
+
+        assert_eq!(
+            board.with_turn(Turn::None).err(),
+            Some(BoardError::GameNotOver)
+        );
+        board.apply_move(Move::Pass).unwrap();
+        board.apply_move(Move::Pass).unwrap();
+        assert!(board.with_turn(Turn::None).is_ok());
+    }
+
+    #[test]
+    fn undoing_the_final_pass_resumes_the_game() {
+        let mut board = empty(3);
+        board.apply_move(Move::Pass).unwrap();
+        board.apply_move(Move::Pass).unwrap();
+        assert_eq!(board.turn, Turn::None);
+
+        board.undo_move().unwrap();
+        assert_eq!(board.turn, Turn::White);
+        assert!(board.passed());
+        assert!(!board.is_terminal());
+
+        let sessions = [
+            AnyEvaluationSession::AlphaBeta(AlphaBetaSession::new(
+                empty(3),
+                2,
+                CacheOption::Disable,
+            )),
+            AnyEvaluationSession::MonteCarlo(
+                MonteCarloSession::new(empty(3), std::time::Duration::from_millis(20)).with_seed(3),
+            ),
+        ];
+        for mut session in sessions {
+            session.apply_move(Move::Pass).unwrap();
+            session.apply_move(Move::Pass).unwrap();
+            assert!(session.evaluate().unwrap().is_empty());
+
+            session.undo_move().unwrap();
+            assert_eq!(session.get_root().turn, Turn::White);
+            let moves = session.evaluate().unwrap();
+            assert!(moves.iter().any(|(mv, _)| *mv == Move::Pass), "{:?}", moves);
+            assert!(moves.len() > 1, "{:?}", moves);
+        }
+    }
+
+    #[test]
+    fn playable_area_leaves_out_dead_points() {
+        let mut board = board(&["#X..#", ".....", "..#..", ".....", "#...#"], Turn::Black);
+        assert_eq!(board.playable_area(), 20);
+        assert_eq!(Board::max_score(&board), 20.5);
+        assert_eq!(board.fill_ratio(), 1.0 / 20.0);
+
+        board.set_komi(-3.0).unwrap();
+        assert_eq!(Board::max_score(&board), 23.0);
+    }
+
+    #[test]
+    fn open_points_count_only_the_empty_playable_points() {
+        let mut board = board(&["#X..#", ".....", "..#..", ".....", "#...#"], Turn::Black);
+        board.set_komi(7.5).unwrap();
+        assert_eq!(board.open_points(), 19.0);
+        board.apply_move(Move::Place(6)).unwrap();
+        assert_eq!(board.open_points(), 18.0);
+    }
+
+    #[test]
+    fn komi_sets_the_sign_of_hand_scored_positions() {
+        let scored = |rows: &[&str], komi: f32| {
+            Board::from_rep(rows.concat(), rows.len() as u8, Turn::White, komi)
+                .unwrap()
+                .calculate_heuristic()
+        };
+        // Black owns all nine points, the rows split evenly, White owns all nine points
+        let black = ["XXX", "X.X", "XXX"];
+        let even = ["OOO", "...", "XXX"];
+        let white = ["OOO", "O.O", "OOO"];
+
+        assert_eq!(scored(&black, 0.5), 8.5);
+        assert_eq!(scored(&black, 9.5), -0.5);
+        assert_eq!(scored(&even, 0.0), 0.0);
+        assert_eq!(scored(&even, 5.5), -5.5);
+        assert_eq!(scored(&white, 0.5), -9.5);
+        assert_eq!(scored(&white, 7.5), -16.5);
+
+        // Black leads on the board but trails after komi, so even its best move scores below zero
+        let mut losing = Board::from_rep(
+            ["XXX.O", "XXX.O", "XXX.O", "XXX.O", "XXX.O"].concat(),
+            5,
+            Turn::Black,
+            12.5,
+        )
+        .unwrap();
+        assert_eq!(losing.calculate_heuristic(), -2.5);
+        let moves = AlphaBeta::new(1, CacheOption::Disable)
+            .evaluate(&mut losing)
+            .unwrap();
+        let best = pick_best(&losing, moves).unwrap();
+        assert!(best.1 < 0.0, "{:?}", best);
+    }
+
+    #[test]
+    fn both_algorithms_take_a_decisive_capture() {
+        // The White column has its last liberty at (2, 1), where it would otherwise escape
+        let capture = board(&["XOX", "XOX", "..."], Turn::Black);
+        let capturing = Move::Place(7);
+
+        let mut alpha_beta = AnyEvaluationSession::AlphaBeta(AlphaBetaSession::new(
+            capture.clone(),
+            2,
+            CacheOption::Disable,
+        ));
+        assert_eq!(alpha_beta.best_move().unwrap().0, capturing);
+
+        let mut monte_carlo = AnyEvaluationSession::MonteCarlo(
+            MonteCarloSession::new(capture, std::time::Duration::from_millis(300)).with_seed(5),
+        );
+        assert_eq!(monte_carlo.best_move().unwrap().0, capturing);
+    }
+
+    #[test]
+    fn winner_passes_out_a_settled_position() {
+        // White can't live in the straight three, so Black owns the board and hasn't passed yet.
+        // Pass only keeps up with filling the three once a pass at the horizon is answered, an
+        // intruder that White passes behind is captured then instead of scored as standing
+        let mut settled = board(&[".X.", "XX.", "XX."], Turn::Black);
+        let moves = AlphaBeta::new(3, CacheOption::Disable)
+            .evaluate(&mut settled)
+            .unwrap();
+        let best = evaluation::pick_best(&settled, moves).unwrap();
+        assert_eq!(best, (Move::Pass, 8.5));
+    }
+
+    #[test]
+    fn rep_errors_point_at_the_problem() {
+        let mut rep = ".".repeat(169);
+        rep.replace_range(47..48, "q");
+        assert_eq!(
+            Board::from_rep(rep, 13, Turn::Black, 0.5).err(),
+            Some(BoardError::InvalidRep(
+                "Invalid char 'q' at position 47 (row 3, col 8)".to_string()
+            ))
+        );
+        assert_eq!(
+            Board::from_rep(".".repeat(24), 5, Turn::Black, 0.5).err(),
+            Some(BoardError::InvalidRep(
+                "Invalid shape: expected 25 characters for size 5, got 24".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn reshape_checks_the_size_before_building() {
+        let rep = "X.O#.....";
+        assert_eq!(Board::reshape_check(rep, 3), Ok(()));
+        assert_eq!(
+            Board::reshape_check(rep, 4),
+            Err(BoardError::InvalidRep(
+                "Invalid shape: expected 16 characters for size 4, got 9".to_string()
+            ))
+        );
+        assert!(Board::reshape_check("", 0).is_err());
+
+        let rows = Board::rep_to_rows(rep, 3);
+        assert_eq!(rows, vec!["X.O", "#..", "..."]);
+        assert_eq!(rows.concat(), rep);
+    }
+
+    // Black to move with one ko capture available at (1, 2), retaken at (1, 1)
+    fn ko() -> Board {
+        board(&[".XO..", "XO.O.", ".XO..", ".....", "....."], Turn::Black)
+    }
+
+    #[test]
+    fn tiny_boards_play_to_the_end() {
+        let one = empty(1);
+        assert_eq!(one.moves().collect::<Vec<_>>(), vec![Move::Pass]);
+        assert_eq!(play_out(one).turn, Turn::None);
+
+        let two = empty(2);
+        assert_eq!(two.moves().count(), 5);
+        assert_eq!(play_out(two).turn, Turn::None);
+    }
+
+    #[test]
+    fn restore_returns_to_the_snapshot() {
+        let mut board = ko();
+        let (rep, hash) = (board.get_rep(), board.get_hash());
+        let snapshot = board.snapshot();
+
+        // The ko capture takes a stone, so restoring has to put it back
+        for mv in [(1, 2), (3, 3), (0, 0)] {
+            board.apply_move(Move::Coords(mv)).unwrap();
+        }
+        board.apply_move(Move::Pass).unwrap();
+        board.restore(snapshot).unwrap();
+        assert_eq!(board.get_rep(), rep);
+        assert_eq!(board.get_hash(), hash);
+        assert_eq!(board.turn, Turn::Black);
+    }
+
+    #[test]
+    fn moves_leave_out_the_ko_retake() {
+        let mut board = ko();
+        board.apply_move(Move::Coords((1, 2))).unwrap();
+        let retake = board.to_place_move(Move::Coords((1, 1)));
+        assert!(!board.moves().any(|m| m == retake));
+        assert_eq!(board.apply_move(retake), Err(BoardError::Repetition));
+    }
+
+    #[test]
+    fn would_repeat_flags_only_the_ko_retake() {
+        let mut board = ko();
+        assert!(!board.would_repeat(Move::Coords((1, 2))));
+        board.apply_move(Move::Coords((1, 2))).unwrap();
+
+        assert!(board.would_repeat(Move::Coords((1, 1))));
+        assert!(!board.would_repeat(Move::Coords((3, 3))));
+        assert!(!board.would_repeat(Move::Pass));
+        // Asking doesn't play anything
+        assert_eq!(board.turn, Turn::White);
+        assert_eq!(board.history.len(), 1);
+    }
+
+    #[test]
+    fn moves_leave_out_a_repeat_without_a_capture() {
+        let mut board = empty(2);
+        for mv in [Move::Place(0), Move::Place(1), Move::Place(2), Move::Pass] {
+            board.apply_move(mv).unwrap();
+        }
+        // Black's 3 captures White's stone, White's 1 then captures all three Black stones
+        board.apply_move(Move::Place(3)).unwrap();
+        board.apply_move(Move::Place(1)).unwrap();
+
+        // Black at 0 captures nothing, but recreates the position White's first move left
+        assert!(!board.captures_at(Move::Place(0), Tile::Black));
+        assert!(!board.moves().any(|m| m == Move::Place(0)));
+        assert!(!board.legal_map()[0]);
+        assert_eq!(
+            board.apply_move(Move::Place(0)),
+            Err(BoardError::Repetition)
+        );
+    }
+
+    #[test]
+    fn history_digest_tells_apart_positions_with_different_retakes() {
+        let mut played = ko();
+        played.apply_move(Move::Coords((1, 2))).unwrap();
+        let fresh = Board::from_rep(played.get_rep(), 5, played.turn, 0.5).unwrap();
+
+        assert_eq!(played.get_hash(), fresh.get_hash());
+        assert_ne!(played.history_digest(), fresh.history_digest());
+        assert_eq!(fresh.history_digest(), empty(3).history_digest());
+    }
+
+    #[test]
+    fn branching_factor_counts_the_legal_moves() {
+        let mut board = ko();
+        assert_eq!(board.branching_factor(), board.moves().count());
+        board.apply_move(Move::Coords((1, 2))).unwrap();
+        assert_eq!(board.branching_factor(), board.moves().count());
+        assert_eq!(empty(3).branching_factor(), 10);
+    }
+
+    #[test]
+    fn bytes_round_trip_smaller_than_the_rep() {
+        for size in [1, 5, 7, 9, 13, 19] {
+            let mut board = empty(size);
+            board.set_komi(-6.5).unwrap();
+            let last = size as usize - 1;
+            for mv in [(0, 0), (last, last), (0, last)] {
+                let _ = board.apply_move(Move::Coords(mv));
+            }
+            if size > 1 {
+                board.apply_move(Move::Coords((last, 0))).unwrap();
+            }
+
+            let bytes = board.to_bytes();
+            let decoded = Board::from_bytes(&bytes).unwrap();
+            assert_eq!(decoded.get_rep(), board.get_rep(), "size {}", size);
+            assert_eq!((decoded.turn, decoded.komi), (board.turn, board.komi));
+            if size >= 5 {
+                assert!(bytes.len() < board.get_rep().len(), "size {}", size);
+            }
+        }
+    }
+
+    #[test]
+    fn display_prints_one_row_per_line() {
+        let board = board(&["X....", ".O...", "..#..", ".....", "....X"], Turn::Black);
+        let printed = board.to_string();
+        let lines = printed.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 5);
+        assert!(lines.iter().all(|l| l.chars().count() == 5));
+        assert_eq!(lines[2], "..#..");
+    }
+
+    #[test]
+    fn place_and_coords_moves_round_trip() {
+        let board = empty(5);
+        assert_eq!(board.to_coords_move(Move::Place(13)), Move::Coords((2, 3)));
+        assert_eq!(board.to_place_move(Move::Coords((2, 3))), Move::Place(13));
+        for pos in 0..25 {
+            let place = Move::Place(pos);
+            assert_eq!(board.to_place_move(board.to_coords_move(place)), place);
+        }
+        assert_eq!(board.to_coords_move(Move::Pass), Move::Pass);
+        assert_eq!(board.to_place_move(Move::Pass), Move::Pass);
+    }
+
+    #[test]
+    fn diagonals_stop_at_the_edges() {
+        let board = empty(5);
+        let diagonals = |pos| {
+            let mut found = board.diagonal_neighbors(pos).collect::<Vec<_>>();
+            found.sort_unstable();
+            found
+        };
+        assert_eq!(diagonals(0), vec![6]);
+        assert_eq!(diagonals(2), vec![6, 8]);
+        assert_eq!(diagonals(12), vec![6, 8, 16, 18]);
+        assert_eq!(board.orthogonal_neighbors(0).count(), 2);
+        assert_eq!(board.orthogonal_neighbors(12).count(), 4);
+    }
+
+    #[test]
+    fn default_reps_detect_the_size() {
+        let board = Board::from_rep_default(".".repeat(49)).unwrap();
+        assert_eq!(
+            (board.size, board.turn, board.komi),
+            (7, Turn::Black, DEFAULT_KOMI)
+        );
+        assert!(matches!(
+            Board::from_rep_default(".".repeat(48)),
+            Err(BoardError::InvalidRep(_))
+        ));
+    }
+
+    #[test]
+    fn moves_as_a_color_check_the_turn() {
+        let mut board = empty(5);
+        assert_eq!(
+            board.apply_move_as(Tile::White, Move::Coords((2, 2))),
+            Err(BoardError::WrongTurn)
+        );
+        assert_eq!(BoardError::WrongTurn.to_string(), "Not your turn");
+        assert!(board.history.is_empty());
+
+        board
+            .apply_move_as(Tile::Black, Move::Coords((2, 2)))
+            .unwrap();
+        assert_eq!(board.get_tile(12), Tile::Black);
+        assert_eq!(board.turn, Turn::White);
+    }
+
+    #[test]
+    fn dead_walls_split_the_board_into_scored_regions() {
+        // Black owns the left region, White the right one
+        let board = board(&["X.#..", ".X#.O", "..#O.", "X.#..", "..#.."], Turn::Black);
+        let components = board.connected_components();
+        assert_eq!(components.len(), 2);
+        assert_eq!(components.iter().map(|c| c.len()).sum::<usize>(), 20);
+        assert!(components[0].iter().all(|p| p % 5 < 2));
+
+        assert_eq!(board.region_scores(), vec![10.0, -10.0]);
+        assert_eq!(board.score(), -0.5);
+        assert_eq!(board.calculate_heuristic(), -0.5);
+    }
+
+    #[test]
+    fn lazy_moves_are_exactly_the_playable_ones() {
+        // Pass, then every placement apply_move accepts, in position order
+        let playable = |board: &Board| {
+            let placements = (0..board.pos_to_chain.len())
+                .filter(|&p| board.clone().apply_move(Move::Place(p)).is_ok())
+                .map(Move::Place);
+            std::iter::once(Move::Pass)
+                .chain(placements)
+                .collect::<Vec<_>>()
+        };
+
+        let mut boards = vec![
+            empty(5),
+            ko(),
+            board(&["#X..#", "X.X..", ".X#O.", "..O.O", "#..O."], Turn::White),
+            board(&[".XXO.", "XX.XO", "XXXOO", "OOOO.", "....."], Turn::Black),
+        ];
+        let mut taken = ko();
+        taken.apply_move(Move::Coords((1, 2))).unwrap();
+        boards.push(taken);
+
+        for board in boards {
+            assert_eq!(board.moves().collect::<Vec<_>>(), playable(&board));
+            assert_eq!(board.moves().next(), Some(Move::Pass));
+        }
+    }
+
+    #[test]
+    fn contested_regions_score_only_their_stones() {
+        // Black alone borders the left region, both colors border the right one
+        let board = board(&["X.#X.", "..#.O", "..#..", "..#..", "..#.."], Turn::Black);
+        assert_eq!(board.region_scores(), vec![10.0, 0.0]);
+        assert_eq!(board.score(), 9.5);
+
+        let ownership = board.ownership();
+        assert!((0..25)
+            .filter(|p| p % 5 < 2)
+            .all(|p| ownership[p] == Some(Tile::Black)));
+        assert_eq!(
+            (ownership[3], ownership[9]),
+            (Some(Tile::Black), Some(Tile::White))
+        );
+        assert!(ownership[4].is_none() && ownership[8].is_none());
+    }
+
+    #[test]
+    fn iter_tiles_walks_every_intersection() {
+        let board = board(&["X..", ".#.", "..O"], Turn::Black);
+        let tiles = board.iter_tiles().collect::<Vec<_>>();
+        assert_eq!(tiles.len(), 9);
+        assert_eq!(tiles[0], ((0, 0), Tile::Black));
+        assert_eq!(tiles[4], ((1, 1), Tile::Dead));
+        assert_eq!(tiles[8], ((2, 2), Tile::White));
+        assert_eq!(tiles[5], ((1, 2), Tile::Free));
+    }
+
+    #[test]
+    fn capturing_gives_a_surrounded_stone_its_liberty() {
+        // (0, 0) has no free neighbor, but takes the last liberty of the White stone at (0, 1)
+        let mut capturing = board(&[".OX", "OX.", "..."], Turn::Black);
+        capturing.apply_move(Move::Coords((0, 0))).unwrap();
+        assert_eq!(capturing.get_tile(1), Tile::Free);
+        let (_, chain) = capturing.get_chain(0).unwrap();
+        assert_eq!(chain.liberty_count(), 1);
+
+        // Without the capture the same move is suicide
+        let mut suicide = board(&[".OX", "OO.", "..."], Turn::Black);
+        assert_eq!(
+            suicide.apply_move(Move::Coords((0, 0))),
+            Err(BoardError::Suicide(Move::Place(0)))
+        );
+    }
+
+    #[test]
+    fn ordered_moves_put_captures_first() {
+        // (2, 1) takes the last liberty of the White column, (4, 4) is a quiet move
+        let board = board(&["XOX..", "XOX..", ".....", ".....", "....."], Turn::Black);
+        let ordered = board.ordered_moves().collect::<Vec<_>>();
+        assert_eq!(ordered[0], Move::Place(11));
+        assert_eq!(ordered.last(), Some(&Move::Pass));
+        assert!(ordered.iter().position(|&m| m == Move::Place(24)).unwrap() > 0);
+
+        assert_eq!(ordered.len(), board.moves().count());
+        assert_eq!(
+            ordered.into_iter().collect::<HashSet<_>>(),
+            board.moves().collect::<HashSet<_>>()
+        );
+    }
+
+    #[test]
+    fn finished_games_have_no_moves_to_order() {
+        let mut board = empty(3);
+        board.apply_move(Move::Pass).unwrap();
+        board.apply_move(Move::Pass).unwrap();
+        assert_eq!(board.turn, Turn::None);
+
+        assert!(board.is_decided());
+        assert_eq!(board.moves().count(), 0);
+        assert_eq!(board.playout_moves().count(), 0);
+        assert_eq!(board.ordered_moves().count(), 0);
+    }
+
+    #[test]
+    fn move_orders_reaching_one_position_are_equal() {
+        let play = |moves: &[(usize, usize)]| {
+            let mut board = empty(5);
+            for &mv in moves {
+                board.apply_move(Move::Coords(mv)).unwrap();
+            }
+            board
+        };
+        // The first order captures a White stone, so the chains end up numbered differently. In
+        // the other one White passes instead of playing that stone
+        let captured = play(&[(0, 1), (0, 0), (1, 0), (4, 4), (2, 2), (3, 3)]);
+        let mut direct = play(&[(1, 0), (4, 4), (0, 1), (3, 3), (2, 2)]);
+        direct.apply_move(Move::Pass).unwrap();
+
+        assert_ne!(captured.pos_to_chain, direct.pos_to_chain);
+        assert!(captured == direct, "{}\n\n{}", captured, direct);
+
+        let mut other_komi = direct.clone();
+        other_komi.set_komi(1.5).unwrap();
+        assert!(other_komi != direct);
+    }
+
+    #[test]
+    fn ataris_list_each_group_with_its_capture_point() {
+        // Black at (0, 0) can be taken at (1, 0), the White pair at (0, 3) and (0, 4) at (0, 2)
+        let board = board(&["XO.OO", "..XXX", ".....", ".....", "....."], Turn::Black);
+        let mut ataris = board.ataris();
+        ataris.sort_by_key(|(_, _, liberty)| *liberty);
+        assert_eq!(
+            ataris,
+            vec![(Tile::White, vec![3, 4], 2), (Tile::Black, vec![0], 5)]
+        );
+    }
+
+    #[test]
+    fn chain_accessors_report_atari_liberties_and_size() {
+        // The White pair only has (0, 0) left, the Black group wrapping it has seven
+        let board = board(&[".OOX.", "XXXX.", ".....", ".....", "....."], Turn::Black);
+        let (_, white) = board.get_chain(1).unwrap();
+        assert!(white.is_in_atari());
+        assert_eq!((white.liberty_count(), white.size()), (1, 2));
+
+        let (_, black) = board.get_chain(3).unwrap();
+        assert!(!black.is_in_atari());
+        assert_eq!((black.liberty_count(), black.size()), (7, 5));
+    }
+
+    #[test]
+    fn each_rejected_move_has_its_own_error() {
+        let mut open = empty(5);
+        open.apply_move(Move::Coords((2, 2))).unwrap();
+        let occupied = Move::Place(12);
+        assert_eq!(
+            open.apply_move(occupied),
+            Err(BoardError::Occupied(occupied))
+        );
+        let outside = Move::Coords((5, 0));
+        assert_eq!(
+            open.apply_move(outside),
+            Err(BoardError::OutOfBounds(outside))
+        );
+
+        // Filling the last liberty of the White pair takes no Black stones
+        let mut atari = board(&[".OOX.", "XXXX.", ".....", ".....", "....."], Turn::White);
+        let suicide = Move::Place(0);
+        assert_eq!(atari.apply_move(suicide), Err(BoardError::Suicide(suicide)));
+
+        let mut ko = ko();
+        ko.apply_move(Move::Coords((1, 2))).unwrap();
+        assert_eq!(
+            ko.apply_move(Move::Coords((1, 1))),
+            Err(BoardError::Repetition)
+        );
+
+        open.apply_move(Move::Pass).unwrap();
+        open.apply_move(Move::Pass).unwrap();
+        let late = Move::Place(0);
+        assert_eq!(open.apply_move(late), Err(BoardError::GameOver(late)));
+
+        assert!(matches!(
+            Board::from_rep("....".to_string(), 5, Turn::Black, 0.5),
+            Err(BoardError::InvalidRep(_))
+        ));
+    }
+
+    #[test]
+    fn fixed_evaluations_repeat_exactly() {
+        // The White row is in atari, Black captures it at (1, 4)
+        let position = || board(&["XXXX.", "OOOO.", "XXXX.", ".....", "....."], Turn::Black);
+        let top = |algorithm, budget| {
+            let mut root = position();
+            let mut moves = evaluation::evaluate_fixed(algorithm, &mut root, budget).unwrap();
+            evaluation::sort_best_first(&root, &mut moves);
+            moves[0]
+        };
+
+        assert_eq!(top("alpha-beta", 2), (Move::Place(9), 13.5));
+        assert_eq!(top("monte-carlo", 400), (Move::Place(9), 41.0));
+
+        let search =
+            |algorithm, budget| evaluation::evaluate_fixed(algorithm, &mut position(), budget);
+        assert_eq!(search("monte-carlo", 400), search("monte-carlo", 400));
+        assert!(search("minimax", 2).is_err());
+    }
+
+    #[test]
+    fn errors_come_back_as_board_errors() {
+        let mut board = empty(3);
+        assert_eq!(
+            board.with_turn(Turn::None).err(),
+            Some(BoardError::GameNotOver)
+        );
+        assert!(matches!(
+            board.set_komi(f32::NAN),
+            Err(BoardError::InvalidKomi(_))
+        ));
+        assert_eq!(
+            board.diff(&empty(5)).err(),
+            Some(BoardError::SizeMismatch(3, 5))
+        );
+        assert!(matches!(
+            Board::from_bytes(&[3]),
+            Err(BoardError::InvalidBytes(_))
+        ));
+
+        let before = board.snapshot();
+        board.apply_move(Move::Coords((0, 0))).unwrap();
+        let after = board.snapshot();
+        board.undo_move().unwrap();
+        assert_eq!(board.restore(after).err(), Some(BoardError::SnapshotAhead));
+        board.apply_move(Move::Coords((1, 1))).unwrap();
+        assert_eq!(
+            board.restore(after).err(),
+            Some(BoardError::SnapshotDiverged)
+        );
+        board.restore(before).unwrap();
+
+        let builder = || builder::BoardBuilder::new().size(3);
+        assert_eq!(
+            builder().black(&[(3, 0)]).build().err(),
+            Some(BoardError::OutOfBounds(Move::Coords((3, 0))))
+        );
+        assert_eq!(
+            builder().black(&[(1, 1)]).white(&[(1, 1)]).build().err(),
+            Some(BoardError::Occupied(Move::Coords((1, 1))))
+        );
+    }
+}
+
+// Run with --no-default-features, the core has to work without serde and, through evaluation's
+// own default features being off, without rayon
+#[cfg(all(test, not(feature = "serde")))]
+mod no_serde_tests {
+    use evaluation::{
+        alphabeta::{AlphaBeta, CacheOption},
+        pick_best, Evaluator, Heuristic,
+    };
+
+    use super::*;
+
+    #[test]
+    fn plays_and_searches_without_serde() {
+        let mut board = Board::from_rep(".".repeat(25), 5, Turn::Black, 0.5).unwrap();
+        board.apply_move(Move::Coords((2, 2))).unwrap();
+        assert_eq!(board.moves().count(), 25);
+        assert_eq!(board.calculate_heuristic(), 24.5);
+
+        let search = AlphaBeta::new(2, CacheOption::Disable)
+            .with_threads(1)
+            .unwrap();
+        let moves = search.evaluate(&mut board).unwrap();
+        assert_eq!(moves.len(), 25);
+        assert!(pick_best(&board, moves).is_some());
+    }
+}
@@ -0,0 +1,204 @@
+use crate::{Board, Move, Tile, Turn};
+
+// GTP column letters, which skip 'I'
+const COLUMNS: &str = "ABCDEFGHJKLMNOPQRSTUVWXYZ";
+
+// Columns are letters from the left, rows are numbers from the bottom, like GTP
+fn to_vertex(board: &Board, mv: Move) -> String {
+    match board.to_coords_move(mv) {
+        Move::Coords((row, col)) => format!(
+            "{}{}",
+            COLUMNS.chars().nth(col).unwrap().to_ascii_lowercase(),
+            board.size as usize - row
+        ),
+        _ => "pass".to_string(),
+    }
+}
+
+fn parse_vertex(s: &str, size: u8) -> Result<Move, String> {
+    if s.eq_ignore_ascii_case("pass") {
+        return Ok(Move::Pass);
+    }
+
+    let mut chars = s.chars();
+    let col = chars
+        .next()
+        .and_then(|c| COLUMNS.find(c.to_ascii_uppercase()))
+        .filter(|c| *c < size as usize);
+    let row = chars
+        .as_str()
+        .parse::<usize>()
+        .ok()
+        .filter(|r| (1..=size as usize).contains(r));
+    match (col, row) {
+        (Some(col), Some(row)) => Ok(Move::Coords((size as usize - row, col))),
+        _ => Err(format!(
+            "Invalid vertex '{}' on a {}x{} board",
+            s, size, size
+        )),
+    }
+}
+
+impl Board {
+    // size:turn:komi|B d4,W c3,B pass with a ':rep' after the komi when the game didn't start
+    // on an empty board
+    pub fn to_replay(&self) -> Result<String, String> {
+        if self.size as usize > COLUMNS.len() {
+            return Err(format!(
+                "Invalid size {}, expected at most {}",
+                self.size,
+                COLUMNS.len()
+            ));
+        }
+
+        let (mut board, moves) = self.unwind().map_err(|e| e.to_string())?;
+        let mut replay = format!(
+            "{}:{}:{}",
+            board.size,
+            board.turn.to_str().to_lowercase(),
+            board.komi
+        );
+        let rep = board.get_rep();
+        if rep.chars().any(|c| c != Tile::Free.to_char()) {
+            replay += ":";
+            replay += &rep;
+        }
+        replay.push('|');
+
+        let mut entries = Vec::with_capacity(moves.len());
+        for mv in moves {
+            let color = match board.turn {
+                Turn::White => "W",
+                _ => "B",
+            };
+            entries.push(format!("{} {}", color, to_vertex(&board, mv)));
+            board.apply_move(mv).map_err(|e| e.to_string())?;
+        }
+        replay += &entries.join(",");
+
+        Ok(replay)
+    }
+
+    pub fn from_replay(replay: &str) -> Result<Board, String> {
+        let (mut board, moves) = Board::parse_replay(replay)?;
+        for (i, (color, mv)) in moves.into_iter().enumerate() {
+            board.apply_move_as(color, mv).map_err(|e| {
+                let color = match color {
+                    Tile::White => "W",
+                    _ => "B",
+                };
+                let entry = format!("{} {}", color, to_vertex(&board, mv));
+                format!("Move {} ({}): {}", i + 1, entry, e)
+            })?;
+        }
+
+        Ok(board)
+    }
+
+    // The starting position and the moves, none of them checked for legality
+    pub fn parse_replay(replay: &str) -> Result<(Board, Vec<(Tile, Move)>), String> {
+        let (header, moves) = replay
+            .trim()
+            .split_once('|')
+            .ok_or("Invalid replay: expected a '|' after the header".to_string())?;
+
+        let fields = header.split(':').map(str::trim).collect::<Vec<_>>();
+        let (size, turn, komi, rep) = match fields.as_slice() {
+            [size, turn, komi] => (*size, *turn, *komi, None),
+            [size, turn, komi, rep] => (*size, *turn, *komi, Some(*rep)),
+            _ => {
+                return Err(format!(
+                    "Invalid replay: expected size:turn:komi[:rep], got '{}'",
+                    header
+                ))
+            }
+        };
+        let size = size
+            .parse::<u8>()
+            .ok()
+            .filter(|s| (1..=COLUMNS.len()).contains(&(*s as usize)))
+            .ok_or(format!(
+                "Invalid size '{}', expected 1 to {}",
+                size,
+                COLUMNS.len()
+            ))?;
+        let turn = Turn::from_str(turn).ok_or(format!(
+            "Invalid turn '{}', expected black, white or none",
+            turn
+        ))?;
+        let komi = komi
+            .parse::<f32>()
+            .ok()
+            .filter(|k| k.is_finite())
+            .ok_or(format!("Invalid komi '{}', expected a number", komi))?;
+        let rep = match rep {
+            Some(rep) => rep.to_string(),
+            None => Tile::Free
+                .to_char()
+                .to_string()
+                .repeat((size as usize).pow(2)),
+        };
+
+        let board = Board::from_rep(rep, size, turn, komi).map_err(|e| e.to_string())?;
+        let entries = moves.split(',').map(str::trim).filter(|e| !e.is_empty());
+        let mut parsed = Vec::new();
+        for (i, entry) in entries.enumerate() {
+            let (color, vertex) = entry.split_once(' ').ok_or(format!(
+                "Move {}: expected '<B|W> <vertex>', got '{}'",
+                i + 1,
+                entry
+            ))?;
+            let color = match color {
+                "B" | "b" => Tile::Black,
+                "W" | "w" => Tile::White,
+                _ => return Err(format!("Move {}: invalid color '{}'", i + 1, color)),
+            };
+            if vertex.trim().eq_ignore_ascii_case("resign") {
+                return Err(format!(
+                    "Move {}: resignation can't be replayed, the board has no resign move",
+                    i + 1
+                ));
+            }
+
+            let mv =
+                parse_vertex(vertex.trim(), size).map_err(|e| format!("Move {}: {}", i + 1, e))?;
+            parsed.push((color, mv));
+        }
+
+        Ok((board, parsed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replays_round_trip_the_game() {
+        let mut board = Board::from_rep(".".repeat(25), 5, Turn::Black, 5.5).unwrap();
+        for p in [1, 0, 5, 12, 13] {
+            board.apply_move(Move::Place(p)).unwrap();
+        }
+        board.apply_move(Move::Pass).unwrap();
+
+        let replay = board.to_replay().unwrap();
+        assert_eq!(replay, "5:black:5.5|B b5,W a5,B a4,W c3,B d3,W pass");
+        let replayed = Board::from_replay(&replay).unwrap();
+        assert!(replayed == board);
+        assert_eq!(replayed.history.len(), 6);
+        assert_eq!(replayed.get_tile(0), Tile::Free);
+    }
+
+    #[test]
+    fn setup_stones_go_in_the_header() {
+        let setup = Board::from_rep("X...O....".to_string(), 3, Turn::White, 0.5).unwrap();
+        let replay = setup.to_replay().unwrap();
+        assert_eq!(replay, "3:white:0.5:X...O....|");
+        assert!(Board::from_replay(&replay).unwrap() == setup);
+
+        assert_eq!(
+            Board::parse_replay("3:black:0.5|B resign").err(),
+            Some("Move 1: resignation can't be replayed, the board has no resign move".to_string())
+        );
+    }
+}
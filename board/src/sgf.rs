@@ -0,0 +1,295 @@
+use crate::{Board, Move, Tile, Turn, DEFAULT_KOMI};
+
+const SGF_SIZE_LIMIT: u8 = 52;
+
+// Only the main line is read, variations and unknown properties are ignored
+fn parse_properties(sgf: &str) -> Result<Vec<(String, Vec<String>)>, String> {
+    let mut chars = sgf.trim().chars().peekable();
+    if chars.next() != Some('(') {
+        return Err("Invalid SGF: expected the game to start with '('".to_string());
+    }
+
+    let mut properties = Vec::new();
+    let mut ident = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            // the first closing paren ends the first variation at every branch
+            ')' => return Ok(properties),
+            '(' | ';' => {}
+            c if c.is_ascii_uppercase() => ident.push(c),
+            c if c.is_whitespace() => {}
+            '[' => {
+                if ident.is_empty() {
+                    return Err("Invalid SGF: value without a property".to_string());
+                }
+
+                let mut values = Vec::new();
+                loop {
+                    let mut value = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('\\') => value.extend(chars.next()),
+                            Some(']') => break,
+                            Some(c) => value.push(c),
+                            None => return Err(format!("Invalid SGF: unclosed value for {ident}")),
+                        }
+                    }
+                    values.push(value);
+
+                    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                        chars.next();
+                    }
+                    if chars.peek() != Some(&'[') {
+                        break;
+                    }
+                    chars.next();
+                }
+                properties.push((std::mem::take(&mut ident), values));
+            }
+            // lowercase letters are allowed in old FF[3] identifiers
+            c if c.is_ascii_lowercase() => {}
+            c => return Err(format!("Invalid SGF: unexpected '{c}'")),
+        }
+    }
+
+    Err("Invalid SGF: expected the game to end with ')'".to_string())
+}
+
+// Letters are column then row, an empty value or 'tt' on small boards is a pass
+fn parse_point(value: &str, size: u8) -> Result<Move, String> {
+    if value.is_empty() || (value == "tt" && size <= 19) {
+        return Ok(Move::Pass);
+    }
+
+    let coords = value
+        .chars()
+        .map(|c| match c {
+            'a'..='z' => Some(c as usize - 'a' as usize),
+            'A'..='Z' => Some(c as usize - 'A' as usize + 26),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>();
+    match coords.as_deref() {
+        Some(&[col, row]) if row < size as usize && col < size as usize => {
+            Ok(Move::Coords((row, col)))
+        }
+        _ => Err(format!("Invalid point '{value}' on a {size}x{size} board")),
+    }
+}
+
+fn to_point(board: &Board, pos: usize) -> String {
+    let letter = |i: usize| match i {
+        0..=25 => (b'a' + i as u8) as char,
+        _ => (b'A' + (i - 26) as u8) as char,
+    };
+    let (row, col) = board.to_coords(pos);
+    format!("{}{}", letter(col), letter(row))
+}
+
+fn single<'a>(ident: &str, values: &'a [String]) -> Result<&'a str, String> {
+    match values {
+        [value] => Ok(value.trim()),
+        _ => Err(format!("Expected a single value for {ident}")),
+    }
+}
+
+impl Board {
+    // Setup stones become the starting position, every move after that is replayed into the history
+    pub fn from_sgf(sgf: &str) -> Result<Board, String> {
+        let (mut board, moves) = Board::parse_sgf(sgf)?;
+        for (i, (color, mv)) in moves.into_iter().enumerate() {
+            board
+                .apply_move_as(color, mv)
+                .map_err(|e| format!("Move {} ({:?}): {}", i + 1, mv, e))?;
+        }
+
+        Ok(board)
+    }
+
+    // The starting position and the moves of the main line, none of them checked for legality
+    pub fn parse_sgf(sgf: &str) -> Result<(Board, Vec<(Tile, Move)>), String> {
+        let properties = parse_properties(sgf)?;
+
+        let mut size = 19;
+        let mut komi = DEFAULT_KOMI;
+        for (ident, values) in properties.iter() {
+            match ident.as_str() {
+                "SZ" => {
+                    let value = single(ident, values)?;
+                    size = value.parse().map_err(|_| {
+                        format!("Invalid size '{value}', only square boards are supported")
+                    })?;
+                }
+                "KM" => {
+                    let value = single(ident, values)?;
+                    komi = value
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|k| k.is_finite())
+                        .ok_or(format!("Invalid komi '{value}', expected a number"))?;
+                }
+                _ => {}
+            }
+        }
+        if size == 0 || size > SGF_SIZE_LIMIT {
+            return Err(format!(
+                "Invalid size {size}, expected 1 to {SGF_SIZE_LIMIT}"
+            ));
+        }
+
+        let mut tiles = vec![Tile::Free; (size as usize).pow(2)];
+        let mut turn = None;
+        let mut moves = Vec::new();
+        for (ident, values) in properties.iter() {
+            let color = match ident.as_str() {
+                "B" | "AB" => Tile::Black,
+                "W" | "AW" => Tile::White,
+                "AE" => Tile::Free,
+                "PL" => {
+                    let value = single(ident, values)?;
+                    let player = match value {
+                        "B" | "b" => Turn::Black,
+                        "W" | "w" => Turn::White,
+                        _ => return Err(format!("Invalid player '{value}', expected B or W")),
+                    };
+                    if moves.is_empty() {
+                        turn = Some(player);
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+
+            if ident.len() == 1 {
+                let mv = parse_point(single(ident, values)?, size)?;
+                moves.push((color, mv));
+                continue;
+            }
+
+            if !moves.is_empty() {
+                return Err(format!("{ident} after the first move is not supported"));
+            }
+            for value in values {
+                if let Move::Coords((row, col)) = parse_point(value, size)? {
+                    tiles[row * size as usize + col] = color;
+                }
+            }
+        }
+
+        let turn = turn.unwrap_or(match moves.first() {
+            Some((Tile::White, _)) => Turn::White,
+            _ => Turn::Black,
+        });
+        let rep = tiles.iter().map(Tile::to_char).collect();
+        Ok((
+            Board::from_rep(rep, size, turn, komi).map_err(|e| e.to_string())?,
+            moves,
+        ))
+    }
+
+    // The starting position becomes setup stones, dead tiles have no SGF equivalent and are left out.
+    // Comments give the move number and any captured stones
+    pub fn to_sgf(&self, comments: bool) -> Result<String, String> {
+        if self.size > SGF_SIZE_LIMIT {
+            return Err(format!(
+                "Invalid size {}, expected 1 to {SGF_SIZE_LIMIT}",
+                self.size
+            ));
+        }
+
+        let (mut board, moves) = self.unwind().map_err(|e| e.to_string())?;
+
+        let mut sgf = format!("(;GM[1]FF[4]SZ[{}]KM[{}]", board.size, board.komi);
+        for (tile, ident) in [(Tile::Black, "AB"), (Tile::White, "AW")] {
+            let stones = (0..board.pos_to_chain.len())
+                .filter(|p| board.get_tile(*p) == tile)
+                .map(|p| format!("[{}]", to_point(&board, p)))
+                .collect::<String>();
+            if !stones.is_empty() {
+                sgf += ident;
+                sgf += &stones;
+            }
+        }
+        match board.turn {
+            Turn::Black => sgf += "PL[B]",
+            Turn::White => sgf += "PL[W]",
+            Turn::None => {}
+        }
+
+        for (i, mv) in moves.into_iter().enumerate() {
+            let color = match board.turn {
+                Turn::White => "W",
+                _ => "B",
+            };
+            let before = board.clone();
+            board.apply_move(mv).map_err(|e| e.to_string())?;
+
+            let point = match board.move_position(mv) {
+                Some(pos) => to_point(&board, pos),
+                None => String::new(),
+            };
+            sgf += &format!(";{color}[{point}]");
+            if !comments {
+                continue;
+            }
+
+            let captured = board
+                .diff(&before)
+                .map_err(|e| e.to_string())?
+                .into_iter()
+                .filter(|(_, old, new)| *old != Tile::Free && *new == Tile::Free)
+                .map(|(p, _, _)| to_point(&board, p))
+                .collect::<Vec<_>>();
+            sgf += &match (mv, captured.len()) {
+                (Move::Pass, _) => format!("C[Move {}: pass]", i + 1),
+                (_, 0) => format!("C[Move {}]", i + 1),
+                (_, 1) => format!("C[Move {}: captured 1 stone ({})]", i + 1, captured[0]),
+                (_, n) => format!(
+                    "C[Move {}: captured {} stones ({})]",
+                    i + 1,
+                    n,
+                    captured.join(", ")
+                ),
+            };
+        }
+        sgf.push(')');
+
+        Ok(sgf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn captures_are_counted_in_the_comments() {
+        let game = "(;GM[1]FF[4]SZ[5]KM[0.5];B[ca];W[aa];B[ab];W[ba];B[bb];W[])";
+        let board = Board::from_sgf(game).unwrap();
+
+        let sgf = board.to_sgf(true).unwrap();
+        assert!(sgf.contains(";B[ab]C[Move 3]"), "{}", sgf);
+        assert!(
+            sgf.contains(";B[bb]C[Move 5: captured 2 stones (aa, ba)]"),
+            "{}",
+            sgf
+        );
+        assert!(sgf.contains(";W[]C[Move 6: pass]"), "{}", sgf);
+
+        let plain = board.to_sgf(false).unwrap();
+        assert!(!plain.contains("C["), "{}", plain);
+        assert!(Board::from_sgf(&sgf).unwrap() == board);
+    }
+
+    #[test]
+    fn komi_has_to_be_a_finite_number() {
+        for komi in ["inf", "-inf", "NaN", "half"] {
+            let sgf = format!("(;SZ[5]KM[{komi}];B[cc])");
+            assert_eq!(
+                Board::parse_sgf(&sgf).err(),
+                Some(format!("Invalid komi '{komi}', expected a number"))
+            );
+        }
+        assert_eq!(Board::from_sgf("(;SZ[5]KM[-6.5])").unwrap().komi, -6.5);
+    }
+}
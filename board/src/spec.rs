@@ -0,0 +1,98 @@
+use crate::{error::BoardError, Board, Turn};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StateSpec {
+    pub rep: String,
+    pub size: u8,
+    pub turn: Turn,
+    pub komi: f32,
+}
+
+impl StateSpec {
+    // rep;size;turn;komi, whitespace around each field is ignored
+    pub fn parse(s: &str) -> Result<StateSpec, String> {
+        let fields = s.trim().split(';').map(str::trim).collect::<Vec<_>>();
+        let [rep, size, turn, komi] = fields.as_slice() else {
+            return Err(format!(
+                "Expected 4 fields separated by ';' (rep;size;turn;komi), got {}",
+                fields.len()
+            ));
+        };
+
+        Ok(StateSpec {
+            rep: rep.to_string(),
+            size: size
+                .parse()
+                .map_err(|_| format!("Invalid size '{}', expected a number", size))?,
+            turn: Turn::from_str(turn).ok_or(format!(
+                "Invalid turn '{}', expected black, white or none",
+                turn
+            ))?,
+            komi: komi
+                .parse::<f32>()
+                .ok()
+                .filter(|k| k.is_finite())
+                .ok_or(format!("Invalid komi '{}', expected a number", komi))?,
+        })
+    }
+
+    pub fn from_board(board: &Board) -> StateSpec {
+        StateSpec {
+            rep: board.get_rep(),
+            size: board.size,
+            turn: board.turn,
+            komi: board.komi,
+        }
+    }
+
+    pub fn to_board(&self) -> Result<Board, BoardError> {
+        Board::from_rep(self.rep.clone(), self.size, self.turn, self.komi)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_are_trimmed_and_typed() {
+        let spec = StateSpec::parse(" X........ ; 3 ; White ; 5.5 \n").unwrap();
+        assert_eq!(
+            spec,
+            StateSpec {
+                rep: "X........".to_string(),
+                size: 3,
+                turn: Turn::White,
+                komi: 5.5,
+            }
+        );
+        assert!(spec.to_board().is_ok());
+    }
+
+    #[test]
+    fn each_malformed_field_has_its_own_error() {
+        assert_eq!(
+            StateSpec::parse(".........;3;black").unwrap_err(),
+            "Expected 4 fields separated by ';' (rep;size;turn;komi), got 3"
+        );
+        assert_eq!(
+            StateSpec::parse(".........;three;black;0.5").unwrap_err(),
+            "Invalid size 'three', expected a number"
+        );
+        assert_eq!(
+            StateSpec::parse(".........;3;red;0.5").unwrap_err(),
+            "Invalid turn 'red', expected black, white or none"
+        );
+        assert_eq!(
+            StateSpec::parse(".........;3;black;half").unwrap_err(),
+            "Invalid komi 'half', expected a number"
+        );
+        assert_eq!(
+            StateSpec::parse(".........;3;black;inf").unwrap_err(),
+            "Invalid komi 'inf', expected a number"
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use crate::{Board, Move, Tile};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// The 8 transforms of a square board, rotations are clockwise
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Symmetry {
+    Identity,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    FlipHorizontal,
+    FlipVertical,
+    Transpose,
+    AntiTranspose,
+}
+
+impl Symmetry {
+    pub const ALL: [Symmetry; 8] = [
+        Symmetry::Identity,
+        Symmetry::Rotate90,
+        Symmetry::Rotate180,
+        Symmetry::Rotate270,
+        Symmetry::FlipHorizontal,
+        Symmetry::FlipVertical,
+        Symmetry::Transpose,
+        Symmetry::AntiTranspose,
+    ];
+
+    pub fn invert(self) -> Symmetry {
+        match self {
+            Symmetry::Rotate90 => Symmetry::Rotate270,
+            Symmetry::Rotate270 => Symmetry::Rotate90,
+            s => s,
+        }
+    }
+
+    // Where (row, col) ends up on a board of the given size
+    pub fn map(self, size: u8, (row, col): (usize, usize)) -> (usize, usize) {
+        let n = size as usize - 1;
+        match self {
+            Symmetry::Identity => (row, col),
+            Symmetry::Rotate90 => (col, n - row),
+            Symmetry::Rotate180 => (n - row, n - col),
+            Symmetry::Rotate270 => (n - col, row),
+            Symmetry::FlipHorizontal => (row, n - col),
+            Symmetry::FlipVertical => (n - row, col),
+            Symmetry::Transpose => (col, row),
+            Symmetry::AntiTranspose => (n - col, n - row),
+        }
+    }
+
+    // Moves keep their kind, out of bounds moves are left for apply_move to reject
+    pub fn apply_move(self, size: u8, mv: Move) -> Move {
+        let total = (size as usize).pow(2);
+        match mv {
+            Move::Coords((row, col)) if row < size as usize && col < size as usize => {
+                Move::Coords(self.map(size, (row, col)))
+            }
+            Move::Place(pos) if pos < total => {
+                let (row, col) = self.map(size, (pos / size as usize, pos % size as usize));
+                Move::Place(row * size as usize + col)
+            }
+            mv => mv,
+        }
+    }
+
+    pub fn invert_move(self, size: u8, mv: Move) -> Move {
+        self.invert().apply_move(size, mv)
+    }
+}
+
+impl Board {
+    fn transformed_rep(&self, symmetry: Symmetry) -> String {
+        let mut tiles = vec![Tile::Dead; self.pos_to_chain.len()];
+        for (coords, tile) in self.iter_tiles() {
+            let (row, col) = symmetry.map(self.size, coords);
+            tiles[row * self.size as usize + col] = tile;
+        }
+        tiles.iter().map(Tile::to_char).collect()
+    }
+
+    // Only the position is transformed, the history is left behind
+    pub fn transformed(&self, symmetry: Symmetry) -> Board {
+        // Transforming a board can't change its shape, so the rep is always valid
+        let mut board = Board::from_rep(
+            self.transformed_rep(symmetry),
+            self.size,
+            self.turn,
+            self.komi,
+        )
+        .unwrap();
+        board.heuristic_mode = self.heuristic_mode;
+        board.maximizing = self.maximizing;
+        board
+    }
+
+    // The transforms that map the dead tiles onto themselves, an asymmetric offline pattern
+    // leaves only the identity
+    pub fn symmetry_group(&self) -> Vec<Symmetry> {
+        let dead = |rep: &str| {
+            rep.chars()
+                .map(|c| c == Tile::Dead.to_char())
+                .collect::<Vec<_>>()
+        };
+        let pattern = dead(&self.get_rep());
+        Symmetry::ALL
+            .into_iter()
+            .filter(|s| dead(&self.transformed_rep(*s)) == pattern)
+            .collect()
+    }
+
+    // The lexicographically smallest rep within the symmetry group, together with the transform
+    // that produces it. Moves found on the canonical board map back with Symmetry::invert_move
+    pub fn canonical_form(&self) -> (Board, Symmetry) {
+        let symmetry = self
+            .symmetry_group()
+            .into_iter()
+            .min_by_key(|s| self.transformed_rep(*s))
+            .unwrap_or(Symmetry::Identity);
+        (self.transformed(symmetry), symmetry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use evaluation::Heuristic;
+
+    use super::*;
+    use crate::Turn;
+
+    #[test]
+    fn rotations_share_a_canonical_form_and_book_moves_map_back() {
+        // Black captures the White corner stone at (1, 4)
+        let rep = ["...XO", "XXXX.", ".....", ".....", "....."].concat();
+        let board = Board::from_rep(rep, 5, Turn::Black, 0.5).unwrap();
+        let rotated = board.transformed(Symmetry::Rotate90);
+        assert!(rotated.get_rep() != board.get_rep());
+
+        let (canonical, symmetry) = board.canonical_form();
+        let (rotated_canonical, rotated_symmetry) = rotated.canonical_form();
+        assert_eq!(canonical.get_rep(), rotated_canonical.get_rep());
+
+        // The book stores the move found on the canonical board
+        let book = canonical
+            .moves()
+            .find(|mv| !canonical.captures_of(*mv).is_empty())
+            .unwrap();
+        assert_eq!(symmetry.invert_move(5, book), Move::Place(9));
+        let rotated_move = rotated_symmetry.invert_move(5, book);
+        assert_eq!(
+            rotated_move,
+            Symmetry::Rotate90.apply_move(5, Move::Place(9))
+        );
+        assert!(!rotated.captures_of(rotated_move).is_empty());
+    }
+
+    #[test]
+    fn offline_tiles_limit_the_symmetries() {
+        let rep = [".#...", ".....", ".....", ".....", "....."].concat();
+        let board = Board::from_rep(rep, 5, Turn::Black, 0.5).unwrap();
+        assert_eq!(board.symmetry_group(), vec![Symmetry::Identity]);
+        assert_eq!(board.canonical_form().1, Symmetry::Identity);
+
+        for symmetry in Symmetry::ALL {
+            let mv = Move::Coords((1, 3));
+            assert_eq!(symmetry.invert_move(5, symmetry.apply_move(5, mv)), mv);
+        }
+    }
+}
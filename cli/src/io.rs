@@ -1,24 +1,61 @@
-use std::{io::Stdin, time::Duration};
+use std::{fs, io::Stdin, time::Duration};
 
-use board::{Board, Move, Turn};
-use evaluation::{alphabeta::AlphaBeta, montecarlo::MonteCarlo, AnyEvaluator};
+use board::{spec::StateSpec, Board, Move};
+use evaluation::{
+    alphabeta::AlphaBeta, montecarlo::MonteCarlo, pick_best, sort_best_first, AnyEvaluator,
+};
+use serde::Serialize;
 
 pub enum Action {
     Undo,
     Mv(Move),
 }
 
-pub struct IO;
+pub struct Problem {
+    pub state: StateSpec,
+    pub expected: Option<Move>,
+}
+
+impl Problem {
+    pub fn matches(&self, board: &Board, mv: Move) -> bool {
+        self.expected
+            .is_some_and(|e| board.to_place_move(e) == board.to_place_move(mv))
+    }
+}
+
+#[derive(Serialize)]
+struct JsonResult {
+    moves: Vec<(Move, f32)>,
+    best: Option<Move>,
+    board: StateSpec,
+}
+
+// In json mode every prompt is left out and results are printed as one JSON object per line
+pub struct IO {
+    pub json: bool,
+}
 impl IO {
-    pub fn read_algorithm(stdin: &Stdin) -> Result<AnyEvaluator, String> {
-        println!("Please choose an algorithm (alpha-beta <depth> | monte-carlo <seconds>):");
+    fn prompt(&self, stdin: &Stdin, message: &str) -> Result<String, String> {
+        if !self.json {
+            println!("{}", message);
+        }
 
         let mut s = String::new();
         stdin.read_line(&mut s).map_err(|e| e.to_string())?;
-        println!("");
+        if !self.json {
+            println!();
+        }
+        Ok(s)
+    }
+
+    pub fn read_algorithm(&self, stdin: &Stdin) -> Result<AnyEvaluator, String> {
+        let s = self.prompt(
+            stdin,
+            "Please choose an algorithm (alpha-beta <depth> | monte-carlo <seconds>):",
+        )?;
 
-        let parts = s.trim().split_whitespace().collect::<Vec<&str>>();
-        if parts.len() == 0 {
+        let parts = s.split_whitespace().collect::<Vec<&str>>();
+        if parts.is_empty() {
             return Err("No arguments given".to_string());
         }
 
@@ -40,54 +77,82 @@ impl IO {
         }
     }
 
-    pub fn read_threads(stdin: &Stdin) -> Result<usize, String> {
-        println!("Please specify how many threads should be used:");
-
-        let mut s = String::new();
-        stdin.read_line(&mut s).map_err(|e| e.to_string())?;
-        println!("");
+    pub fn read_threads(&self, stdin: &Stdin) -> Result<usize, String> {
+        let s = self.prompt(stdin, "Please specify how many threads should be used:")?;
 
         s.trim()
             .parse::<usize>()
             .map_err(|_| "Threads are invalid".to_string())
     }
 
-    pub fn read_state(stdin: &Stdin) -> Result<(String, u8, Turn, f32), String> {
-        println!("Please input the starting state (rep;size;turn;komi):");
+    // Json mode takes {"rep": ..., "size": ..., "turn": ..., "komi": ...} on a single line
+    pub fn read_state(&self, stdin: &Stdin) -> Result<StateSpec, String> {
+        let s = self.prompt(
+            stdin,
+            "Please input the starting state (rep;size;turn;komi):",
+        )?;
+        self.parse_state(&s)
+    }
 
-        let mut s = String::new();
-        stdin.read_line(&mut s).map_err(|e| e.to_string())?;
-        println!("");
+    pub fn parse_state(&self, s: &str) -> Result<StateSpec, String> {
+        if !self.json {
+            return StateSpec::parse(s);
+        }
 
-        let splits = s
-            .trim()
-            .to_lowercase()
-            .split(";")
-            .map(|a| a.to_string())
-            .collect::<Vec<_>>();
-        if splits.len() != 4 {
-            return Err("Please input 4 things separated by semicolons".to_string());
+        let state: StateSpec =
+            serde_json::from_str(s.trim()).map_err(|e| format!("Invalid state: {}", e))?;
+        if !state.komi.is_finite() {
+            return Err(format!("Invalid komi '{}', expected a number", state.komi));
         }
+        Ok(state)
+    }
+
+    fn parse_coords(s: &str) -> Result<(usize, usize), String> {
+        let (x, y) = s.trim().split_once(',').ok_or("Missing ','".to_string())?;
+        Ok((
+            x.trim().parse().map_err(|_| "X is not a valid number")?,
+            y.trim().parse().map_err(|_| "Y is not a valid number")?,
+        ))
+    }
 
-        let rep = splits[0].trim().to_string();
-        let size = splits[1]
-            .parse::<u8>()
-            .map_err(|_| "Size is not a number".to_string())?;
-        let turn = Turn::from_str(splits[2].as_str()).ok_or("Turn is not valid".to_string())?;
-        let komi = splits[3]
-            .trim()
-            .parse::<f32>()
-            .map_err(|_| "Komi is not a number".to_string())?;
+    // One problem per line as rep;size;turn;komi with an optional ;x,y or ;pass answer.
+    // Empty lines and lines starting with '//' are skipped.
+    pub fn parse_problems(content: &str) -> Result<Vec<Problem>, String> {
+        content
+            .lines()
+            .enumerate()
+            .filter(|(_, l)| !l.trim().is_empty() && !l.trim().starts_with("//"))
+            .map(|(i, l)| {
+                let splits = l.trim().split(";").collect::<Vec<_>>();
+                if splits.len() != 4 && splits.len() != 5 {
+                    return Err(format!(
+                        "Line {}: expected 4 or 5 things separated by semicolons",
+                        i + 1
+                    ));
+                }
 
-        Ok((rep, size, turn, komi))
+                let state = StateSpec::parse(&splits[..4].join(";"))
+                    .map_err(|e| format!("Line {}: {}", i + 1, e))?;
+                let expected = match splits.get(4).map(|s| s.trim().to_lowercase()) {
+                    None => None,
+                    Some(s) if s == "pass" => Some(Move::Pass),
+                    Some(s) => Some(Move::Coords(
+                        IO::parse_coords(&s).map_err(|e| format!("Line {}: {}", i + 1, e))?,
+                    )),
+                };
+
+                Ok(Problem { state, expected })
+            })
+            .collect()
     }
 
-    pub fn read_action(stdin: &Stdin, board: &Board) -> Result<Action, String> {
-        println!("Please input the next action (pass | x,y | undo):");
+    pub fn read_problems(path: &str) -> Result<Vec<Problem>, String> {
+        let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        IO::parse_problems(&content)
+    }
 
-        let mut s = String::new();
-        stdin.read_line(&mut s).map_err(|e| e.to_string())?;
-        println!("");
+    pub fn read_action(&self, stdin: &Stdin, board: &Board) -> Result<Action, String> {
+        let s = self.prompt(stdin, "Please input the next action (pass | x,y | undo):")?;
 
         if s.trim().to_lowercase() == "pass" {
             return Ok(Action::Mv(Move::Pass));
@@ -97,64 +162,147 @@ impl IO {
             return Ok(Action::Undo);
         }
 
-        let (x, y) = s.trim().split_once(',').ok_or("Missing ','".to_string())?;
-        Ok(Action::Mv(Move::Place(board.to_pos(
-            x.parse().map_err(|_| "X is not a valid number")?,
-            y.parse().map_err(|_| "Y is not a valid number")?,
-        ))))
+        let (x, y) = IO::parse_coords(&s)?;
+        Ok(Action::Mv(Move::Place(board.to_pos(x, y))))
     }
 
-    pub fn press_enter_continue(stdin: &Stdin) {
+    pub fn press_enter_continue(&self, stdin: &Stdin) {
+        if self.json {
+            return;
+        }
+
         println!("Press Enter to continue...");
         let mut s = String::new();
         stdin.read_line(&mut s).unwrap();
     }
 
-    pub fn print_move_evalutations(
-        root: &Board,
-        moves: Vec<(Move, f32)>,
-        maximizing: bool,
-        time: Duration,
-    ) {
-        println!("Move evaluations ({} seconds):", time.as_secs());
-
-        let mut sorted: Vec<_> = moves.iter().collect();
-        sorted.sort_by(|a, b| a.1.total_cmp(&b.1));
-        if maximizing {
-            sorted.reverse();
+    pub fn print_move_evalutations(&self, root: &Board, moves: Vec<(Move, f32)>, time: Duration) {
+        if self.json {
+            println!("{}", IO::to_json(root, moves));
+            return;
         }
 
+        println!(
+            "Move evaluations ({} seconds, positive favors Black):",
+            time.as_secs()
+        );
+
+        let mut sorted = moves;
+        sort_best_first(root, &mut sorted);
+
         let width = (sorted.len() as f32).log10().floor() as usize + 1;
         for (i, (mv, eval)) in sorted.iter().enumerate() {
             println!(
                 "{:width$}: {:12} | {:+05.1}",
                 i,
-                match mv {
+                match root.to_coords_move(*mv) {
                     Move::Coords((x, y)) => format!("Place {}, {}", x, y),
-                    Move::Place(a) => {
-                        let coords = root.to_coords(*a);
-                        format!("Place {}, {}", coords.0, coords.1)
-                    }
-                    Move::Pass => "Pass".to_string(),
+                    _ => "Pass".to_string(),
                 },
                 eval
             );
         }
     }
 
-    pub fn print_result(board: &Board) {
-        println!(
-            "{}",
-            board
-                .get_rep()
-                .char_indices()
-                .fold(String::new(), |mut acc, (i, c)| {
-                    if i > 0 && (i % board.size as usize) == 0 {
-                        acc.push('\n');
-                    }
-                    acc.push(c);
-                    acc
-                })
+    // The board is printed together with the evaluations in json mode
+    pub fn print_result(&self, board: &Board) {
+        if !self.json {
+            println!("{}", board);
+        }
+    }
+
+    pub fn print_game_over(&self, board: &Board) {
+        match self.json {
+            true => println!("{}", IO::to_json(board, Vec::new())),
+            false => println!("The game is over"),
+        }
+    }
+
+    // Moves are given as coordinates like in the tables, best is null without any moves
+    pub fn to_json(root: &Board, moves: Vec<(Move, f32)>) -> String {
+        let moves = moves
+            .into_iter()
+            .map(|(mv, eval)| (root.to_coords_move(mv), eval))
+            .collect::<Vec<_>>();
+        let result = JsonResult {
+            best: pick_best(root, moves.clone()).map(|(mv, _)| mv),
+            moves,
+            board: StateSpec::from_board(root),
+        };
+        serde_json::to_string(&result).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use board::Turn;
+
+    use super::*;
+
+    const PROBLEMS: &str = "// ladder problems
+.........;3;black;0.5;1,1
+
+X........;3;White;-2;pass
+";
+
+    #[test]
+    fn problem_files_parse_one_problem_per_line() {
+        let problems = IO::parse_problems(PROBLEMS).unwrap();
+        assert_eq!(problems.len(), 2);
+        assert_eq!(problems[0].state.turn, Turn::Black);
+        assert_eq!(problems[0].expected, Some(Move::Coords((1, 1))));
+        assert_eq!(
+            (problems[1].state.komi, problems[1].expected),
+            (-2.0, Some(Move::Pass))
+        );
+
+        let error = IO::parse_problems("\n.........;3;black").err().unwrap();
+        assert!(error.starts_with("Line 2:"), "{}", error);
+    }
+
+    #[test]
+    fn evaluations_serialize_with_coordinates_and_the_best_move() {
+        let board = Board::from_rep(".........".to_string(), 3, Turn::Black, 0.5).unwrap();
+        let json = IO::to_json(&board, vec![(Move::Pass, -0.5), (Move::Place(4), 2.5)]);
+        let value = serde_json::from_str::<serde_json::Value>(&json).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "moves": [["Pass", -0.5], [{"Coords": [1, 1]}, 2.5]],
+                "best": {"Coords": [1, 1]},
+                "board": {"rep": ".........", "size": 3, "turn": "Black", "komi": 0.5},
+            })
         );
+
+        let empty = serde_json::from_str::<serde_json::Value>(&IO::to_json(&board, Vec::new()));
+        assert_eq!(empty.unwrap()["best"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn states_parse_as_json_lines_in_json_mode() {
+        let json = IO { json: true };
+        let line = r#"{"rep":"X........","size":3,"turn":"White","komi":-2.0}"#;
+        let state = json.parse_state(line).unwrap();
+        assert_eq!(state, StateSpec::parse("X........;3;white;-2").unwrap());
+        assert!(json.parse_state("X........;3;white;-2").is_err());
+
+        let text = IO { json: false };
+        assert_eq!(text.parse_state("X........;3;white;-2"), Ok(state));
+        assert!(text.parse_state(line).is_err());
+    }
+
+    #[test]
+    fn answers_match_in_either_move_form() {
+        let problems = IO::parse_problems(PROBLEMS).unwrap();
+        let board = problems[0].state.to_board().unwrap();
+        assert!(problems[0].matches(&board, Move::Place(4)));
+        assert!(problems[0].matches(&board, Move::Coords((1, 1))));
+        assert!(!problems[0].matches(&board, Move::Pass));
+
+        let open = Problem {
+            state: problems[1].state.clone(),
+            expected: None,
+        };
+        assert!(!open.matches(&board, Move::Pass));
     }
 }
@@ -1,45 +1,56 @@
 use std::{
+    env::args,
     io::stdin,
     thread::{self},
     time::{Duration, Instant},
 };
 
-use board::Board;
-use evaluation::{AnyEvaluator, Evaluator, Heuristic};
+use evaluation::{pick_best, AnyEvaluator, Evaluator, Heuristic};
 use io::{Action, IO};
-use rayon::ThreadPoolBuilder;
 
 mod io;
 
 fn main() -> Result<(), String> {
+    let mut arg_list = args().collect::<Vec<_>>();
+    let komi = take_komi(&mut arg_list)?;
+    let io = IO {
+        json: take_flag(&mut arg_list, "--json"),
+    };
+
     let sin = stdin();
-    let evaluator: AnyEvaluator = IO::read_algorithm(&sin)?;
+    let mut evaluator: AnyEvaluator = io.read_algorithm(&sin)?;
 
     if evaluator.is_multi_threaded() {
-        let threads = IO::read_threads(&sin)?;
-        ThreadPoolBuilder::new()
-            .num_threads(threads)
-            .build_global()
-            .unwrap();
+        let threads = io.read_threads(&sin)?;
+        evaluator = evaluator.with_threads(threads)?;
     }
 
-    let (rep, size, turn, komi) = IO::read_state(&sin)?;
+    if arg_list.get(1).is_some_and(|a| a == "problems") {
+        let path = arg_list
+            .get(2)
+            .ok_or("No problem file provided".to_string())?;
+        return solve_problems(&io, &evaluator, path, komi);
+    }
 
-    let mut board = Board::from_rep(rep, size, turn, komi)?;
+    let mut state = io.read_state(&sin)?;
+    state.komi = komi.unwrap_or(state.komi);
+    let mut board = state.to_board().map_err(|e| e.to_string())?;
 
+    // Errors go to stderr in both modes, the pause only gives a person time to read them
+    let pause = if io.json { 0 } else { 2000 };
     while !board.is_terminal() {
-        IO::print_result(&board);
+        io.print_result(&board);
 
         let start = Instant::now();
         let move_evaluation = evaluator.evaluate(&mut board)?;
         let end = Instant::now();
 
-        IO::print_move_evalutations(&board, move_evaluation, board.is_maximizing(), end - start);
+        io.print_move_evalutations(&board, move_evaluation, end - start);
 
-        let action = IO::read_action(&sin, &board);
+        let action = io.read_action(&sin, &board);
         if let Err(e) = action {
             eprintln!("Error: {}", e);
-            thread::sleep(Duration::from_millis(2000));
+            thread::sleep(Duration::from_millis(pause));
             continue;
         }
 
@@ -47,23 +58,149 @@ fn main() -> Result<(), String> {
             Action::Mv(mv) => {
                 if let Err(e) = board.apply_move(mv) {
                     eprintln!("Error: {}", e);
-                    thread::sleep(Duration::from_millis(2000));
+                    thread::sleep(Duration::from_millis(pause));
                     continue;
                 }
             }
             Action::Undo => {
                 if let Err(e) = board.undo_move() {
                     eprintln!("Error: {}", e);
-                    thread::sleep(Duration::from_millis(2000));
+                    thread::sleep(Duration::from_millis(pause));
                     continue;
                 }
             }
         }
 
-        IO::press_enter_continue(&sin);
+        io.press_enter_continue(&sin);
+    }
+
+    io.print_game_over(&board);
+
+    Ok(())
+}
+
+// "--json" switches to scripting mode, and is removed from the list
+fn take_flag(arg_list: &mut Vec<String>, flag: &str) -> bool {
+    let Some(i) = arg_list.iter().position(|a| a == flag) else {
+        return false;
+    };
+    arg_list.remove(i);
+    true
+}
+
+// "--komi <value>" overrides the komi of every state read, and is removed from the list
+fn take_komi(arg_list: &mut Vec<String>) -> Result<Option<f32>, String> {
+    let Some(i) = arg_list.iter().position(|a| a == "--komi") else {
+        return Ok(None);
+    };
+
+    let value = arg_list
+        .get(i + 1)
+        .ok_or("No value provided for --komi".to_string())?;
+    let komi = value
+        .parse::<f32>()
+        .ok()
+        .filter(|k| k.is_finite())
+        .ok_or(format!("Invalid komi '{}', expected a number", value))?;
+    arg_list.drain(i..=i + 1);
+    Ok(Some(komi))
+}
+
+// Json mode prints the evaluation of each problem instead of the pass/fail report
+fn solve_problems(
+    io: &IO,
+    evaluator: &AnyEvaluator,
+    path: &str,
+    komi: Option<f32>,
+) -> Result<(), String> {
+    let mut problems = IO::read_problems(path)?;
+    if let Some(komi) = komi {
+        problems.iter_mut().for_each(|p| p.state.komi = komi);
+    }
+
+    let mut checked = 0;
+    let mut solved = 0;
+    for (i, problem) in problems.iter().enumerate() {
+        let mut board = problem.state.to_board().map_err(|e| e.to_string())?;
+
+        let moves = evaluator.evaluate(&mut board)?;
+        if io.json {
+            println!("{}", IO::to_json(&board, moves));
+            continue;
+        }
+
+        println!("Problem {}:", i + 1);
+        io.print_result(&board);
+
+        let (best, score) = pick_best(&board, moves).ok_or("No moves to evaluate".to_string())?;
+        println!("Engine: {:?} ({:+.1})", board.to_coords_move(best), score);
+
+        if let Some(expected) = problem.expected {
+            checked += 1;
+            if problem.matches(&board, best) {
+                solved += 1;
+                println!("PASS");
+            } else {
+                println!("FAIL, expected {:?}", expected);
+            }
+        }
+        println!();
+    }
+
+    if io.json {
+        return Ok(());
     }
 
-    println!("The game is over");
+    println!(
+        "Solved {} of {} problems with a known answer",
+        solved, checked
+    );
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use board::spec::StateSpec;
+
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn komi_and_flags_are_taken_out_of_the_arguments() {
+        let mut arg_list = args(&["cli", "--komi", "-3.5", "problems", "--json", "p.txt"]);
+        assert_eq!(take_komi(&mut arg_list), Ok(Some(-3.5)));
+        assert!(take_flag(&mut arg_list, "--json"));
+        assert_eq!(arg_list, args(&["cli", "problems", "p.txt"]));
+
+        assert_eq!(take_komi(&mut arg_list), Ok(None));
+        assert!(!take_flag(&mut arg_list, "--json"));
+    }
+
+    #[test]
+    fn invalid_komi_is_rejected() {
+        for value in ["half", "NaN", "inf"] {
+            let mut arg_list = args(&["cli", "--komi", value]);
+            assert_eq!(
+                take_komi(&mut arg_list),
+                Err(format!("Invalid komi '{}', expected a number", value))
+            );
+        }
+        assert_eq!(
+            take_komi(&mut args(&["cli", "--komi"])),
+            Err("No value provided for --komi".to_string())
+        );
+    }
+
+    #[test]
+    fn states_that_arent_square_are_rejected() {
+        let state = StateSpec::parse("........;3;black;0.5").unwrap();
+        assert!(matches!(
+            state.to_board().err(),
+            Some(board::error::BoardError::InvalidRep(_))
+        ));
+    }
+}
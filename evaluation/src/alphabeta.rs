@@ -1,10 +1,30 @@
-use rayon::prelude::*;
+#[cfg(feature = "parallel")]
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
 use std::{
     collections::{HashMap, VecDeque},
-    sync::{Arc, Mutex},
+    fs,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+    time::{Duration, Instant},
 };
 
-use crate::{EvaluationSession, Evaluator, Heuristic};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::{
+    finite_score, EvalError, EvaluationSession, Evaluator, Heuristic, MoveRestriction, Strength,
+};
+
+// key, depth, reached, bound and value
+const TABLE_ENTRY_BYTES: usize = 8 + 1 + 1 + 1 + 4;
+// Memory an entry takes in the table, its map slot with the control byte, padded for the map's
+// load factor, and its key again in the insertion order
+const TABLE_ENTRY_MEMORY: usize =
+    (size_of::<(u64, TranspositionEntry)>() + 1) * 8 / 7 + size_of::<u64>();
+// A position this wide gets half of the time budget
+const TYPICAL_BRANCHING: usize = 32;
 
 #[derive(Clone, Copy, Debug)]
 pub enum Bound {
@@ -18,6 +38,23 @@ pub struct TranspositionEntry {
     pub depth: u8,
     pub value: f32,
     pub bound: Bound,
+    pub reached: u8,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MoveEvaluation<A> {
+    pub mv: A,
+    pub value: f32,
+    // plies actually searched along the principal variation, including the move itself
+    pub depth: u8,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TableStats {
+    pub stored: usize,
+    pub capacity: usize,
+    pub hits: usize,
+    pub misses: usize,
 }
 
 #[derive(Default)]
@@ -25,68 +62,307 @@ pub struct TranspositionTable {
     capacity: usize,
     entries: HashMap<u64, TranspositionEntry>,
     inserted: VecDeque<u64>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
 }
 
 impl TranspositionTable {
+    // Nothing is allocated up front, the table grows with its entries up to the capacity
     pub fn new(capacity: usize) -> Self {
         Self {
             capacity,
-            entries: HashMap::with_capacity(capacity),
-            inserted: VecDeque::with_capacity(capacity),
+            entries: HashMap::new(),
+            inserted: VecDeque::new(),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
         }
     }
 
-    pub fn get(&mut self, key: u64, depth: u8) -> Option<TranspositionEntry> {
-        if *self.inserted.front().unwrap_or(&u64::MAX) == key {
-            self.inserted.pop_front();
-            self.inserted.push_back(key);
-        }
-        self.entries.get(&key).and_then(|entry| {
+    // How many entries fit in that many bytes once the table is full
+    pub fn capacity_for_bytes(bytes: usize) -> usize {
+        bytes / TABLE_ENTRY_MEMORY
+    }
+
+    // Entries are evicted in insertion order, so lookups never have to touch the queue
+    pub fn get(&self, key: u64, depth: u8) -> Option<TranspositionEntry> {
+        let entry = self.entries.get(&key).and_then(|entry| {
             if entry.depth >= depth {
                 Some(*entry)
             } else {
                 None
             }
-        })
+        });
+
+        let counter = if entry.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        entry
     }
 
     pub fn insert(&mut self, key: u64, entry: TranspositionEntry) {
-        if self.entries.len() >= self.capacity {
-            let removal = self.inserted.pop_front().unwrap();
-            self.entries.remove(&removal);
+        if self.capacity == 0 {
+            return;
         }
 
-        self.entries.insert(key, entry);
-        self.inserted.push_back(key);
+        // Overwriting a stored key doesn't need room
+        if !self.entries.contains_key(&key) && self.entries.len() >= self.capacity {
+            if let Some(removal) = self.inserted.pop_front() {
+                self.entries.remove(&removal);
+            }
+        }
+
+        if self.entries.insert(key, entry).is_none() {
+            self.inserted.push_back(key);
+        }
     }
 
     pub fn len(&self) -> usize {
         self.entries.len()
     }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.inserted.clear();
+        self.hits.store(0, Ordering::Relaxed);
+        self.misses.store(0, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> TableStats {
+        TableStats {
+            stored: self.entries.len(),
+            capacity: self.capacity,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Entries are written oldest first, so a loaded table evicts in the same order. Keys come from
+    // the std hasher, a table is only worth loading into a build with the same Rust version
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), EvalError> {
+        let mut bytes = Vec::with_capacity(self.inserted.len() * TABLE_ENTRY_BYTES);
+        for key in self.inserted.iter() {
+            let entry = self.entries[key];
+            bytes.extend(key.to_le_bytes());
+            bytes.push(entry.depth);
+            bytes.push(entry.reached);
+            bytes.push(match entry.bound {
+                Bound::Exact => 0,
+                Bound::LowerBound => 1,
+                Bound::UpperBound => 2,
+            });
+            bytes.extend(entry.value.to_le_bytes());
+        }
+        fs::write(path, bytes).map_err(|e| EvalError::Io(e.to_string()))
+    }
+
+    // Only the newest entries are kept when the file holds more than the capacity
+    pub fn load(path: impl AsRef<Path>, capacity: usize) -> Result<Self, EvalError> {
+        let mut table = TranspositionTable::new(capacity);
+        table.load_entries(path)?;
+        Ok(table)
+    }
+
+    fn load_entries(&mut self, path: impl AsRef<Path>) -> Result<(), EvalError> {
+        let bytes = fs::read(path).map_err(|e| EvalError::Io(e.to_string()))?;
+        if bytes.len() % TABLE_ENTRY_BYTES != 0 {
+            return Err(EvalError::InvalidTable(format!(
+                "Invalid table: {} bytes is not a whole number of {} byte entries",
+                bytes.len(),
+                TABLE_ENTRY_BYTES
+            )));
+        }
+        if self.capacity == 0 {
+            return Ok(());
+        }
+
+        for chunk in bytes.chunks_exact(TABLE_ENTRY_BYTES) {
+            let bound = match chunk[10] {
+                0 => Bound::Exact,
+                1 => Bound::LowerBound,
+                2 => Bound::UpperBound,
+                b => return Err(EvalError::InvalidTable(format!("Invalid bound byte {}", b))),
+            };
+            let key = u64::from_le_bytes(chunk[0..8].try_into().unwrap());
+            let entry = TranspositionEntry {
+                depth: chunk[8],
+                reached: chunk[9],
+                bound,
+                value: f32::from_le_bytes(chunk[11..].try_into().unwrap()),
+            };
+            self.insert(key, entry);
+        }
+        Ok(())
+    }
+}
+
+// The part of the budget a position gets, growing with its branching factor. Wide positions
+// need the time to get as deep, narrow ones reach their depth in a fraction of it
+pub fn time_share(budget: Duration, branching: usize) -> Duration {
+    budget.mul_f64(branching as f64 / (branching + TYPICAL_BRANCHING) as f64)
 }
 
 pub enum CacheOption {
     Capacity(usize),
+    Shared(Arc<RwLock<TranspositionTable>>),
     Disable,
 }
 
 #[derive(Clone)]
 pub struct AlphaBeta {
     depth: u8,
-    table: Option<Arc<Mutex<TranspositionTable>>>,
+    // positions at least this full are searched to the given depth instead
+    endgame: Option<(f32, u8)>,
+    // searched one ply deeper at a time until the position's share of this is used up
+    time: Option<Duration>,
+    // equally valued root moves are shuffled with this seed, None keeps generation order
+    tie_seed: Option<u64>,
+    restriction: Option<MoveRestriction>,
+    table: Option<Arc<RwLock<TranspositionTable>>>,
+    #[cfg(feature = "parallel")]
+    pool: Option<Arc<ThreadPool>>,
 }
 
 impl AlphaBeta {
     pub fn new(depth: u8, cache: CacheOption) -> Self {
         let table = match cache {
-            CacheOption::Capacity(a) => Some(Arc::new(Mutex::new(TranspositionTable::new(a)))),
+            CacheOption::Capacity(a) => Some(Arc::new(RwLock::new(TranspositionTable::new(a)))),
+            CacheOption::Shared(t) => Some(t),
             CacheOption::Disable => None,
         };
-        Self { depth, table }
+        Self {
+            depth,
+            endgame: None,
+            time: None,
+            tie_seed: None,
+            restriction: None,
+            table,
+            #[cfg(feature = "parallel")]
+            pool: None,
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    pub fn with_threads(self, threads: usize) -> Result<Self, EvalError> {
+        if threads < 1 {
+            return Err(EvalError::InvalidParam(
+                "At least one thread is required".to_string(),
+            ));
+        }
+
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .map_err(|e| EvalError::ThreadPool(e.to_string()))?;
+        Ok(self.with_pool(Arc::new(pool)))
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    pub fn with_threads(self, threads: usize) -> Result<Self, EvalError> {
+        match threads {
+            0 => Err(EvalError::InvalidParam(
+                "At least one thread is required".to_string(),
+            )),
+            1 => Ok(self),
+            _ => Err(EvalError::InvalidParam(
+                "Built without the 'parallel' feature".to_string(),
+            )),
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    pub fn with_pool(mut self, pool: Arc<ThreadPool>) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    pub fn with_endgame(mut self, threshold: f32, depth: u8) -> Self {
+        self.endgame = Some((threshold, depth));
+        self
+    }
+
+    pub fn with_time_budget(mut self, budget: Duration) -> Self {
+        self.time = Some(budget);
+        self
+    }
+
+    pub fn with_tie_shuffle(mut self, seed: Option<u64>) -> Self {
+        self.tie_seed = seed;
+        self
+    }
+
+    pub fn with_strength(mut self, strength: Strength) -> Self {
+        self.depth = strength.depth(self.depth);
+        self
+    }
+
+    // Fills the table from a file written by TranspositionTable::save, up to its capacity
+    pub fn with_warm_table(self, path: impl AsRef<Path>) -> Result<Self, EvalError> {
+        let table = self.table.as_ref().ok_or(EvalError::InvalidParam(
+            "A warm table needs the cache to be enabled".to_string(),
+        ))?;
+        table.write().unwrap().load_entries(path)?;
+        Ok(self)
+    }
+
+    pub fn with_move_restriction(mut self, restriction: Option<MoveRestriction>) -> Self {
+        self.restriction = restriction;
+        self
+    }
+
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    pub fn endgame(&self) -> Option<(f32, u8)> {
+        self.endgame
+    }
+
+    pub fn time_budget(&self) -> Option<Duration> {
+        self.time
+    }
+
+    pub fn tie_seed(&self) -> Option<u64> {
+        self.tie_seed
+    }
+
+    pub fn move_restriction(&self) -> Option<MoveRestriction> {
+        self.restriction
+    }
+
+    fn depth_for<T: Heuristic>(&self, root: &T) -> u8 {
+        match self.endgame {
+            Some((threshold, depth)) if root.fill_ratio() >= threshold => depth.min(self.depth),
+            _ => self.depth,
+        }
+    }
+
+    pub fn has_cache(&self) -> bool {
+        self.table.is_some()
+    }
+
+    pub fn table(&self) -> Option<Arc<RwLock<TranspositionTable>>> {
+        self.table.clone()
+    }
+
+    pub fn table_stats(&self) -> Option<TableStats> {
+        self.table.as_ref().map(|t| t.read().unwrap().stats())
+    }
+
+    pub fn clear_cache(&self) {
+        if let Some(table) = self.table.as_ref() {
+            table.write().unwrap().clear();
+        }
     }
 
     pub fn stored_states(&self) -> usize {
-        self.table.clone().map_or(0, |t| t.lock().unwrap().len())
+        self.table.as_ref().map_or(0, |t| t.read().unwrap().len())
     }
 
     fn alpha_beta<T: Heuristic>(
@@ -95,27 +371,27 @@ impl AlphaBeta {
         depth: u8,
         mut alpha: f32,
         mut beta: f32,
-    ) -> f32 {
+    ) -> (f32, u8) {
         let key = node.get_hash();
 
         if let Some(entry) = self
             .table
             .as_ref()
-            .map(|t| t.lock().unwrap().get(key, depth))
-            .flatten()
+            .and_then(|t| t.read().unwrap().get(key, depth))
         {
             match entry.bound {
-                Bound::Exact => return entry.value,
+                Bound::Exact => return (entry.value, entry.reached),
                 Bound::LowerBound => alpha = alpha.max(entry.value),
-                Bound::UpperBound => beta = beta.max(entry.value),
+                Bound::UpperBound => beta = beta.min(entry.value),
             }
             if alpha >= beta {
-                return entry.value;
+                return (entry.value, entry.reached);
             }
         }
 
-        if depth == 0 || node.is_terminal() {
-            return node.calculate_heuristic();
+        // A pass at the horizon is searched one ply further, so an answering pass gets scored
+        if node.is_terminal() || (depth == 0 && !node.passed()) {
+            return (node.calculate_heuristic(), 0);
         }
 
         let original_alpha = alpha;
@@ -124,20 +400,32 @@ impl AlphaBeta {
         } else {
             f32::INFINITY
         };
+        let mut best_reached = 0;
+
+        let moves = node.ordered_moves().collect::<Vec<_>>();
+        if moves.len() <= 1 && node.is_decided() {
+            return (node.calculate_heuristic(), 0);
+        }
 
-        let moves = node.moves().collect::<Vec<_>>();
         for mv in moves {
             if node.play(mv).is_err() {
                 continue;
             }
 
-            let value = self.alpha_beta(node, depth - 1, alpha, beta);
+            let (value, reached) = self.alpha_beta(node, depth.saturating_sub(1), alpha, beta);
             node.undo().unwrap();
+            let improved = if node.is_maximizing() {
+                value > best_value
+            } else {
+                value < best_value
+            };
+            if improved {
+                best_value = value;
+                best_reached = reached.saturating_add(1);
+            }
             if node.is_maximizing() {
-                best_value = best_value.max(value);
                 alpha = alpha.max(best_value);
             } else {
-                best_value = best_value.min(value);
                 beta = beta.min(best_value);
             }
             if alpha >= beta {
@@ -153,37 +441,145 @@ impl AlphaBeta {
             Bound::Exact
         };
 
-        if let Some(mut table) = self.table.as_ref().map(|t| t.lock().unwrap()) {
+        if let Some(mut table) = self.table.as_ref().map(|t| t.write().unwrap()) {
             table.insert(
                 key,
                 TranspositionEntry {
                     depth,
                     value: best_value,
                     bound,
+                    reached: best_reached,
                 },
             );
         }
 
-        best_value
+        (best_value, best_reached)
+    }
+
+    pub fn evaluate_detailed<T: Heuristic>(
+        &self,
+        root: &mut T,
+    ) -> Result<Vec<MoveEvaluation<T::Action>>, EvalError> {
+        let moves = match self.restriction.and_then(|r| r.root_moves(root)) {
+            Some(moves) => moves,
+            None => root.moves().collect::<Vec<_>>(),
+        };
+        self.evaluate_moves(root, moves)
+    }
+
+    fn evaluate_moves<T: Heuristic>(
+        &self,
+        root: &mut T,
+        moves: Vec<T::Action>,
+    ) -> Result<Vec<MoveEvaluation<T::Action>>, EvalError> {
+        let depth = self.depth_for(root);
+        let Some(budget) = self.time else {
+            return self.evaluate_moves_at(root, moves, depth);
+        };
+
+        // Each ply multiplies the work by up to the branching factor, so a deeper iteration is
+        // only started if that many times the last one still fits in the share
+        let branching = root.branching_factor();
+        let share = time_share(budget, branching);
+        let start = Instant::now();
+        let mut results = self.evaluate_moves_at(root, moves.clone(), 0)?;
+        let mut last = start.elapsed();
+        for depth in 1..=depth {
+            let expected = last.saturating_mul(branching.max(1) as u32);
+            if start.elapsed().saturating_add(expected) >= share {
+                break;
+            }
+            let iteration = Instant::now();
+            results = self.evaluate_moves_at(root, moves.clone(), depth)?;
+            last = iteration.elapsed();
+        }
+        Ok(results)
+    }
+
+    fn evaluate_moves_at<T: Heuristic>(
+        &self,
+        root: &mut T,
+        moves: Vec<T::Action>,
+        depth: u8,
+    ) -> Result<Vec<MoveEvaluation<T::Action>>, EvalError> {
+        let root = &*root;
+        let evaluate_move = |m: T::Action| {
+            let mut copy = root.clone();
+            copy.play(m).ok()?;
+            let (value, reached) = self.alpha_beta(&mut copy, depth, f32::MIN, f32::MAX);
+            Some(MoveEvaluation {
+                mv: m,
+                value: finite_score(value),
+                depth: reached.saturating_add(1),
+            })
+        };
+
+        #[cfg(feature = "parallel")]
+        let mut results: Vec<_> = {
+            let search = || moves.into_par_iter().filter_map(evaluate_move).collect();
+            match self.pool.as_ref() {
+                Some(pool) => pool.install(search),
+                None => search(),
+            }
+        };
+
+        #[cfg(not(feature = "parallel"))]
+        let mut results = moves
+            .into_iter()
+            .filter_map(evaluate_move)
+            .collect::<Vec<_>>();
+
+        if let Some(seed) = self.tie_seed {
+            shuffle_ties(&mut results, &mut StdRng::seed_from_u64(seed));
+        }
+        Ok(results)
+    }
+}
+
+// Moves with exactly the same value swap places among themselves, everything else stays put
+fn shuffle_ties<A: Copy, R: Rng + ?Sized>(results: &mut [MoveEvaluation<A>], rng: &mut R) {
+    let mut done = vec![false; results.len()];
+    for i in 0..results.len() {
+        if done[i] {
+            continue;
+        }
+
+        let slots = (i..results.len())
+            .filter(|&j| results[j].value == results[i].value)
+            .collect::<Vec<_>>();
+        let mut entries = slots.iter().map(|&j| results[j]).collect::<Vec<_>>();
+        entries.shuffle(rng);
+        for (&slot, entry) in slots.iter().zip(entries) {
+            results[slot] = entry;
+            done[slot] = true;
+        }
     }
 }
 
 impl Evaluator for AlphaBeta {
-    fn evaluate<T: Heuristic>(&self, root: &mut T) -> Result<Vec<(T::Action, f32)>, String> {
-        let moves = root.moves().collect::<Vec<_>>();
-        Ok(moves
-            .into_par_iter()
-            .filter_map(|m| {
-                let mut copy = root.clone();
-                copy.play(m).ok()?;
-                let eval = self.alpha_beta(&mut copy, self.depth, f32::MIN, f32::MAX);
-                Some((m, eval))
-            })
+    fn evaluate<T: Heuristic>(&self, root: &mut T) -> Result<Vec<(T::Action, f32)>, EvalError> {
+        Ok(self
+            .evaluate_detailed(root)?
+            .into_iter()
+            .map(|e| (e.mv, e.value))
+            .collect())
+    }
+
+    fn evaluate_candidates<T: Heuristic>(
+        &self,
+        root: &mut T,
+        candidates: &[T::Action],
+    ) -> Result<Vec<(T::Action, f32)>, EvalError> {
+        let moves = root.moves().filter(|mv| candidates.contains(mv)).collect();
+        Ok(self
+            .evaluate_moves(root, moves)?
+            .into_iter()
+            .map(|e| (e.mv, e.value))
             .collect())
     }
 
     fn is_multi_threaded(&self) -> bool {
-        true
+        cfg!(feature = "parallel")
     }
 }
 
@@ -191,6 +587,7 @@ impl Evaluator for AlphaBeta {
 pub struct AlphaBetaSession<T: Heuristic> {
     pub root: T,
     evaluator: AlphaBeta,
+    strength: Option<Strength>,
 }
 
 impl<T: Heuristic> AlphaBetaSession<T> {
@@ -198,23 +595,104 @@ impl<T: Heuristic> AlphaBetaSession<T> {
         Self {
             root,
             evaluator: AlphaBeta::new(depth, cache),
+            strength: None,
         }
     }
+
+    #[cfg(feature = "parallel")]
+    pub fn with_pool(mut self, pool: Arc<ThreadPool>) -> Self {
+        self.evaluator = self.evaluator.with_pool(pool);
+        self
+    }
+
+    pub fn with_endgame(mut self, threshold: f32, depth: u8) -> Self {
+        self.evaluator = self.evaluator.with_endgame(threshold, depth);
+        self
+    }
+
+    pub fn with_time_budget(mut self, budget: Duration) -> Self {
+        self.evaluator = self.evaluator.with_time_budget(budget);
+        self
+    }
+
+    pub fn with_tie_shuffle(mut self, seed: Option<u64>) -> Self {
+        self.evaluator = self.evaluator.with_tie_shuffle(seed);
+        self
+    }
+
+    pub fn with_move_restriction(mut self, restriction: Option<MoveRestriction>) -> Self {
+        self.evaluator = self.evaluator.with_move_restriction(restriction);
+        self
+    }
+
+    pub fn with_strength(mut self, strength: Strength) -> Self {
+        self.evaluator = self.evaluator.with_strength(strength);
+        self.strength = Some(strength);
+        self
+    }
+
+    pub fn depth(&self) -> u8 {
+        self.evaluator.depth()
+    }
+
+    pub fn endgame(&self) -> Option<(f32, u8)> {
+        self.evaluator.endgame()
+    }
+
+    pub fn time_budget(&self) -> Option<Duration> {
+        self.evaluator.time_budget()
+    }
+
+    pub fn tie_seed(&self) -> Option<u64> {
+        self.evaluator.tie_seed()
+    }
+
+    pub fn move_restriction(&self) -> Option<MoveRestriction> {
+        self.evaluator.move_restriction()
+    }
+
+    pub fn has_cache(&self) -> bool {
+        self.evaluator.has_cache()
+    }
+
+    pub fn table(&self) -> Option<Arc<RwLock<TranspositionTable>>> {
+        self.evaluator.table()
+    }
+
+    pub fn table_stats(&self) -> Option<TableStats> {
+        self.evaluator.table_stats()
+    }
+
+    pub fn evaluate_detailed(&mut self) -> Result<Vec<MoveEvaluation<T::Action>>, EvalError> {
+        self.evaluator.evaluate_detailed(&mut self.root)
+    }
 }
 
 impl<T: Heuristic> EvaluationSession<T> for AlphaBetaSession<T> {
-    fn apply_move(&mut self, mv: <T as Heuristic>::Action) -> Result<(), String> {
+    fn apply_move(&mut self, mv: <T as Heuristic>::Action) -> Result<(), T::Error> {
         self.root.play(mv)
     }
 
-    fn undo_move(&mut self) -> Result<(), String> {
+    fn undo_move(&mut self) -> Result<(), T::Error> {
         self.root.undo()
     }
 
-    fn evaluate(&mut self) -> Result<Vec<(<T as Heuristic>::Action, f32)>, String> {
+    fn strength(&self) -> Option<Strength> {
+        self.strength
+    }
+
+    fn evaluate(&mut self) -> Result<Vec<(<T as Heuristic>::Action, f32)>, EvalError> {
         self.evaluator.evaluate(&mut self.root)
     }
 
+    fn evaluate_candidates(
+        &mut self,
+        candidates: &[<T as Heuristic>::Action],
+    ) -> Result<Vec<(<T as Heuristic>::Action, f32)>, EvalError> {
+        self.evaluator
+            .evaluate_candidates(&mut self.root, candidates)
+    }
+
     fn is_multi_threaded(&self) -> bool {
         self.evaluator.is_multi_threaded()
     }
@@ -222,4 +700,232 @@ impl<T: Heuristic> EvaluationSession<T> for AlphaBetaSession<T> {
     fn get_root(&self) -> &T {
         &self.root
     }
+
+    fn get_root_mut(&mut self) -> &mut T {
+        &mut self.root
+    }
+
+    // Cache keys cover everything that affects a score, and the table may be shared
+    fn clear_search(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::PickGame;
+
+    fn entry(depth: u8, value: f32) -> TranspositionEntry {
+        TranspositionEntry {
+            depth,
+            value,
+            bound: Bound::Exact,
+            reached: depth,
+        }
+    }
+
+    #[test]
+    fn only_a_seed_reorders_equal_root_moves() {
+        let order = |seed: Option<u64>| {
+            AlphaBeta::new(1, CacheOption::Disable)
+                .with_tie_shuffle(seed)
+                .evaluate_detailed(&mut PickGame::new(&[1.0, 5.0, 5.0, 5.0, 0.0], 1))
+                .unwrap()
+                .into_iter()
+                .map(|e| e.mv)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(order(None), vec![0, 1, 2, 3, 4]);
+        assert!((0..5).all(|_| order(None) == order(None)));
+        assert_eq!(order(Some(9)), order(Some(9)));
+
+        let shuffled = (0..20).map(|seed| order(Some(seed))).collect::<Vec<_>>();
+        assert!(shuffled.iter().any(|o| *o != order(None)));
+        for o in shuffled {
+            assert_eq!((o[0], o[4]), (0, 4), "{:?}", o);
+        }
+    }
+
+    #[test]
+    fn lookups_are_read_only_and_depth_gated() {
+        let mut table = TranspositionTable::new(4);
+        table.insert(1, entry(3, 2.0));
+
+        // get only needs a shared reference, several readers can hold one at once
+        let shared = &table;
+        let (a, b) = (shared.get(1, 3), shared.get(1, 2));
+        assert_eq!(a.map(|e| e.value), Some(2.0));
+        assert_eq!(b.map(|e| e.value), Some(2.0));
+        assert!(shared.get(1, 4).is_none());
+        assert!(shared.get(2, 0).is_none());
+
+        let stats = table.stats();
+        assert_eq!((stats.hits, stats.misses), (2, 2));
+    }
+
+    #[test]
+    fn tables_grow_with_their_entries() {
+        let mut table = TranspositionTable::new(TranspositionTable::capacity_for_bytes(1 << 40));
+        assert_eq!(table.entries.capacity(), 0);
+        table.insert(1, entry(1, 1.0));
+        assert!(table.entries.capacity() < 1024);
+
+        let per_entry = size_of::<(u64, TranspositionEntry)>() + size_of::<u64>();
+        let capacity = TranspositionTable::capacity_for_bytes(1_000_000);
+        assert!(capacity > 0 && capacity * per_entry <= 1_000_000);
+    }
+
+    #[test]
+    fn zero_capacity_stores_nothing() {
+        let mut table = TranspositionTable::new(0);
+        table.insert(1, entry(1, 1.0));
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn full_table_evicts_the_oldest_entry() {
+        let mut table = TranspositionTable::new(2);
+        table.insert(1, entry(1, 1.0));
+        table.insert(2, entry(1, 2.0));
+        table.insert(1, entry(2, 3.0));
+        assert_eq!(table.len(), 2);
+
+        table.insert(3, entry(1, 4.0));
+        assert!(table.get(1, 0).is_none());
+        assert!(table.get(2, 0).is_some());
+        assert!(table.get(3, 0).is_some());
+    }
+
+    fn deepest<T: Heuristic>(evaluator: &AlphaBeta, root: &mut T) -> u8 {
+        let results = evaluator.evaluate_detailed(root).unwrap();
+        results.iter().map(|e| e.depth).max().unwrap()
+    }
+
+    #[test]
+    fn wider_positions_get_more_of_the_budget() {
+        let budget = Duration::from_millis(200);
+        let (narrow, wide) = (time_share(budget, 2), time_share(budget, 8));
+        assert!(narrow < wide && wide < budget);
+
+        // The wide position searched with its own share, then as if it only got the narrow one
+        let mut root = PickGame::new(&[1.0; 8], 12);
+        let search = |budget| AlphaBeta::new(12, CacheOption::Disable).with_time_budget(budget);
+        let as_narrow = narrow.mul_f64((8 + TYPICAL_BRANCHING) as f64 / 8.0);
+        let own = deepest(&search(budget), &mut root);
+        let cut = deepest(&search(as_narrow), &mut root);
+        assert!(own >= cut);
+        assert!(own > 1);
+    }
+
+    #[test]
+    fn evaluators_with_their_own_thread_counts_agree() {
+        let root = PickGame::new(&[3.0, 1.0, 7.0, 2.0], 2);
+        let evaluate = |threads| {
+            let evaluator = AlphaBeta::new(2, CacheOption::Disable)
+                .with_threads(threads)
+                .unwrap();
+            let mut results = evaluator.evaluate(&mut root.clone()).unwrap();
+            results.sort_by_key(|(mv, _)| *mv);
+            results
+        };
+
+        let single = evaluate(1);
+        assert_eq!(
+            crate::pick_best(&root, single.clone()).map(|b| b.0),
+            Some(2)
+        );
+        #[cfg(feature = "parallel")]
+        assert_eq!(evaluate(3), single);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn single_thread_pool_matches_the_global_pool() {
+        let mut root = PickGame::new(&[3.0, 1.0, 7.0, 2.0, 5.0], 4);
+        let search = AlphaBeta::new(4, CacheOption::Disable);
+        let pool = ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+
+        let mut global = search.evaluate(&mut root).unwrap();
+        let mut single = search
+            .with_pool(Arc::new(pool))
+            .evaluate(&mut root)
+            .unwrap();
+        global.sort_by_key(|(mv, _)| *mv);
+        single.sort_by_key(|(mv, _)| *mv);
+        assert_eq!(global, single);
+    }
+
+    #[test]
+    fn saved_tables_load_back_and_warm_identical_searches() {
+        let path = std::env::temp_dir().join(format!("ipvgo-warm-{}.bin", std::process::id()));
+        let mut table = TranspositionTable::new(4);
+        table.insert(1, entry(3, 2.5));
+        table.insert(2, entry(1, -1.0));
+        table.insert(
+            3,
+            TranspositionEntry {
+                depth: 2,
+                value: 7.0,
+                bound: Bound::LowerBound,
+                reached: 4,
+            },
+        );
+        table.save(&path).unwrap();
+
+        let loaded = TranspositionTable::load(&path, 4).unwrap();
+        assert_eq!(loaded.inserted, table.inserted);
+        for key in table.inserted.iter() {
+            let (a, b) = (table.entries[key], loaded.entries[key]);
+            assert_eq!((a.depth, a.value, a.reached), (b.depth, b.value, b.reached));
+            assert_eq!(format!("{:?}", a.bound), format!("{:?}", b.bound));
+        }
+
+        // Only the newest entries fit in a smaller table
+        let capped = TranspositionTable::load(&path, 2).unwrap();
+        assert_eq!(capped.inserted, VecDeque::from([2, 3]));
+
+        let root = PickGame::new(&[3.0, 1.0, 7.0, 2.0], 3);
+        let search = |evaluator: AlphaBeta| {
+            let mut results = evaluator.evaluate(&mut root.clone()).unwrap();
+            results.sort_by_key(|(mv, _)| *mv);
+            results
+        };
+        let cold = AlphaBeta::new(3, CacheOption::Capacity(1024));
+        let cold_results = search(cold.clone());
+        cold.table().unwrap().read().unwrap().save(&path).unwrap();
+
+        let warm = AlphaBeta::new(3, CacheOption::Capacity(1024))
+            .with_warm_table(&path)
+            .unwrap();
+        assert!(!warm.table().unwrap().read().unwrap().is_empty());
+        assert_eq!(search(warm), cold_results);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn table_files_and_threads_report_eval_errors() {
+        let path = std::env::temp_dir().join(format!("ipvgo-table-{}.bin", std::process::id()));
+        let _ = fs::remove_file(&path);
+        assert!(matches!(
+            TranspositionTable::load(&path, 4),
+            Err(EvalError::Io(_))
+        ));
+
+        fs::write(&path, [0; TABLE_ENTRY_BYTES + 1]).unwrap();
+        assert!(matches!(
+            TranspositionTable::load(&path, 4),
+            Err(EvalError::InvalidTable(_))
+        ));
+        fs::remove_file(&path).unwrap();
+
+        let disabled = AlphaBeta::new(1, CacheOption::Disable);
+        assert!(matches!(
+            disabled.clone().with_warm_table(&path),
+            Err(EvalError::InvalidParam(_))
+        ));
+        assert!(matches!(
+            disabled.with_threads(0),
+            Err(EvalError::InvalidParam(_))
+        ));
+    }
 }
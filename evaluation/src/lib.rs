@@ -1,32 +1,370 @@
-use std::fmt::Debug;
+use std::{
+    fmt::{self, Debug, Display},
+    hash::{Hash, Hasher},
+    time::Duration,
+};
+
+use rand::{rng, rngs::StdRng, seq::IteratorRandom, Rng, SeedableRng};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 pub mod alphabeta;
 pub mod montecarlo;
 
 pub trait Heuristic: Send + Sync + Clone {
     type Action: Debug + Copy + Send + Sync + PartialEq;
+    // What play and undo report, sessions hand it back unchanged
+    type Error: Debug + Display;
 
+    // Scores are from the maximizing side's perspective, positive means it is ahead
     fn calculate_heuristic(&self) -> f32;
+    fn max_score(&self) -> f32;
     fn is_terminal(&self) -> bool;
+    fn is_decided(&self) -> bool {
+        false
+    }
     fn is_maximizing(&self) -> bool;
+    fn for_side_to_move(&self, score: f32) -> f32 {
+        if self.is_maximizing() {
+            score
+        } else {
+            -score
+        }
+    }
+    fn passed(&self) -> bool {
+        false
+    }
+    // How much of the position is settled, from 0 to 1
+    fn fill_ratio(&self) -> f32 {
+        0.0
+    }
+    // The most a side can still gain by playing, komi and anything already settled left out
+    fn open_points(&self) -> f32 {
+        (1.0 - self.fill_ratio()) * self.max_score()
+    }
+    // Moves played so far in the game
+    fn move_number(&self) -> usize {
+        0
+    }
+    // The line a placement is on, the edge is line 1. None for anything that isn't a placement
+    fn line_of(&self, _mv: Self::Action) -> Option<usize> {
+        None
+    }
+    // How many moves the side to move has, a measure of how wide the search gets
+    fn branching_factor(&self) -> usize {
+        self.moves().count()
+    }
     fn get_hash(&self) -> u64;
     fn moves(&self) -> impl Iterator<Item = Self::Action>;
-    fn play(&mut self, mv: Self::Action) -> Result<(), String>;
-    fn undo(&mut self) -> Result<(), String>;
+    fn playout_moves(&self) -> impl Iterator<Item = Self::Action> {
+        self.moves()
+    }
+    // Same moves as moves(), with the most promising first for better cutoffs
+    fn ordered_moves(&self) -> impl Iterator<Item = Self::Action> {
+        self.moves()
+    }
+    fn play(&mut self, mv: Self::Action) -> Result<(), Self::Error>;
+    fn undo(&mut self) -> Result<(), Self::Error>;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EvalError {
+    NoMoves,
+    InvalidParam(String),
+    // The pool couldn't be built, with rayon's reason
+    ThreadPool(String),
+    // Reading or writing a table file failed, with the reason from the OS
+    Io(String),
+    InvalidTable(String),
+}
+
+impl Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalError::NoMoves => f.write_str("No moves to evaluate"),
+            EvalError::InvalidParam(message) | EvalError::InvalidTable(message) => {
+                f.write_str(message)
+            }
+            EvalError::ThreadPool(message) => write!(f, "Couldn't start the threads: {}", message),
+            EvalError::Io(message) => write!(f, "Couldn't access the table file: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+impl From<EvalError> for String {
+    fn from(error: EvalError) -> Self {
+        error.to_string()
+    }
+}
+
+// NaN becomes an even score and infinities the largest finite ones, so sorting and JSON output
+// never see a non-finite value
+pub fn finite_score(score: f32) -> f32 {
+    if score.is_nan() {
+        return 0.0;
+    }
+    score.clamp(f32::MIN, f32::MAX)
+}
+
+// The first of several equally scored moves wins, keeping evaluator order meaningful
+pub fn pick_best<T: Heuristic>(root: &T, moves: Vec<(T::Action, f32)>) -> Option<(T::Action, f32)> {
+    moves.into_iter().reduce(|best, mv| {
+        if root.for_side_to_move(mv.1) > root.for_side_to_move(best.1) {
+            mv
+        } else {
+            best
+        }
+    })
+}
+
+// Best first for the side to move, equal scores keep the evaluator order
+pub fn sort_best_first<T: Heuristic>(root: &T, moves: &mut [(T::Action, f32)]) {
+    moves.sort_by(|a, b| {
+        root.for_side_to_move(b.1)
+            .total_cmp(&root.for_side_to_move(a.1))
+    });
+}
+
+// Any move within epsilon of the best is chosen at random, so equally good moves vary.
+// pick_best stays the deterministic choice
+pub fn pick_best_random<T: Heuristic, R: Rng + ?Sized>(
+    root: &T,
+    moves: Vec<(T::Action, f32)>,
+    epsilon: f32,
+    rng: &mut R,
+) -> Option<(T::Action, f32)> {
+    let (_, best) = pick_best(root, moves.clone())?;
+    let top = root.for_side_to_move(best);
+    moves
+        .into_iter()
+        .filter(|(_, score)| top - root.for_side_to_move(*score) <= epsilon)
+        .choose(rng)
+}
+
+// Keeps root placements off the outer lines for the first moves of a game. Only the search root
+// is narrowed, legality and the rest of the tree are unaffected
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MoveRestriction {
+    // moves into the game the restriction lasts
+    pub moves: usize,
+    // outer lines that are left out, 1 only excludes the edge
+    pub lines: usize,
+}
+
+impl MoveRestriction {
+    // The root moves to search, None once the restriction is over or if it would leave no
+    // placement at all
+    pub fn root_moves<T: Heuristic>(&self, root: &T) -> Option<Vec<T::Action>> {
+        if root.move_number() >= self.moves {
+            return None;
+        }
+
+        let allowed = root
+            .moves()
+            .filter(|&mv| root.line_of(mv).is_none_or(|l| l > self.lines))
+            .collect::<Vec<_>>();
+        allowed
+            .iter()
+            .any(|&mv| root.line_of(mv).is_some())
+            .then_some(allowed)
+    }
+}
+
+// Weaker settings search less and sometimes play a move other than the best
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Strength {
+    Beginner,
+    Intermediate,
+    Strong,
+}
+
+impl Strength {
+    pub fn depth(&self, depth: u8) -> u8 {
+        match self {
+            Strength::Beginner => depth.min(1),
+            Strength::Intermediate => depth.min(3),
+            Strength::Strong => depth,
+        }
+    }
+
+    pub fn time(&self, time: Duration) -> Duration {
+        match self {
+            Strength::Beginner => time / 8,
+            Strength::Intermediate => time / 2,
+            Strength::Strong => time,
+        }
+    }
+
+    // Monte-carlo scores are spread out by visit share, so the best move stands out less
+    pub fn temperature(&self) -> Option<f32> {
+        match self {
+            Strength::Beginner => Some(1.0),
+            Strength::Intermediate => Some(0.5),
+            Strength::Strong => None,
+        }
+    }
+
+    // Chance of playing a random other move instead of the best one
+    pub fn mistake_rate(&self) -> f64 {
+        match self {
+            Strength::Beginner => 0.25,
+            Strength::Intermediate => 0.1,
+            Strength::Strong => 0.0,
+        }
+    }
+
+    pub fn pick<T: Heuristic, R: Rng + ?Sized>(
+        &self,
+        root: &T,
+        moves: Vec<(T::Action, f32)>,
+        epsilon: Option<f32>,
+        rng: &mut R,
+    ) -> Option<(T::Action, f32)> {
+        let best = match epsilon {
+            Some(epsilon) => pick_best_random(root, moves.clone(), epsilon, rng)?,
+            None => pick_best(root, moves.clone())?,
+        };
+        if !rng.random_bool(self.mistake_rate()) {
+            return Some(best);
+        }
+
+        moves
+            .into_iter()
+            .filter(|(mv, _)| *mv != best.0)
+            .choose(rng)
+            .or(Some(best))
+    }
+}
+
+// The move to play, weakened settings sometimes pick another one and full strength always
+// plays the best. With an epsilon the best is any move within it, otherwise the first best move
+pub fn pick_for_strength<T: Heuristic, R: Rng + ?Sized>(
+    root: &T,
+    moves: Vec<(T::Action, f32)>,
+    strength: Option<Strength>,
+    epsilon: Option<f32>,
+    rng: &mut R,
+) -> Option<(T::Action, f32)> {
+    if let Some(strength) = strength {
+        return strength.pick(root, moves, epsilon, rng);
+    }
+    match epsilon {
+        Some(epsilon) => pick_best_random(root, moves, epsilon, rng),
+        None => pick_best(root, moves),
+    }
+}
+
+const FIXED_SEED: u64 = 0x1e60;
+
+// Budget is the depth for alpha-beta and the iteration count for monte-carlo.
+// Results don't depend on timing or threads, so they can be compared exactly
+pub fn evaluate_fixed<T: Heuristic>(
+    algorithm: &str,
+    root: &mut T,
+    budget: usize,
+) -> Result<Vec<(T::Action, f32)>, EvalError> {
+    match algorithm {
+        "alpha-beta" => {
+            let depth = u8::try_from(budget)
+                .map_err(|_| EvalError::InvalidParam("Depth is invalid".to_string()))?;
+            alphabeta::AlphaBeta::new(depth, alphabeta::CacheOption::Disable).evaluate(root)
+        }
+        "monte-carlo" => {
+            let evaluator = montecarlo::MonteCarlo::new(Duration::ZERO);
+            let mut rng = StdRng::seed_from_u64(FIXED_SEED);
+            Ok(evaluator.evaluate_iterations(root, budget, &mut rng))
+        }
+        any => Err(EvalError::InvalidParam(format!(
+            "Invalid algorithm '{any}'"
+        ))),
+    }
 }
 
 pub trait Evaluator {
-    fn evaluate<T: Heuristic>(&self, root: &mut T) -> Result<Vec<(T::Action, f32)>, String>;
+    fn evaluate<T: Heuristic>(&self, root: &mut T) -> Result<Vec<(T::Action, f32)>, EvalError>;
+    // Only the root moves in candidates are evaluated
+    fn evaluate_candidates<T: Heuristic>(
+        &self,
+        root: &mut T,
+        candidates: &[T::Action],
+    ) -> Result<Vec<(T::Action, f32)>, EvalError>;
     fn is_multi_threaded(&self) -> bool;
 }
 
 pub trait EvaluationSession<T: Heuristic>: Clone {
     fn get_root(&self) -> &T;
-    fn evaluate(&mut self) -> Result<Vec<(T::Action, f32)>, String>;
+    fn get_root_mut(&mut self) -> &mut T;
+    fn evaluate(&mut self) -> Result<Vec<(T::Action, f32)>, EvalError>;
+    fn evaluate_sorted(&mut self) -> Result<Vec<(T::Action, f32)>, EvalError> {
+        let mut moves = self.evaluate()?;
+        sort_best_first(self.get_root(), &mut moves);
+        Ok(moves)
+    }
+    fn evaluate_candidates(
+        &mut self,
+        candidates: &[T::Action],
+    ) -> Result<Vec<(T::Action, f32)>, EvalError>;
     fn is_multi_threaded(&self) -> bool;
+    fn strength(&self) -> Option<Strength>;
+    fn best_move(&mut self) -> Result<(T::Action, f32), EvalError> {
+        let moves = self.evaluate()?;
+        pick_for_strength(self.get_root(), moves, self.strength(), None, &mut rng())
+            .ok_or(EvalError::NoMoves)
+    }
+    fn best_move_random<R: Rng + ?Sized>(
+        &mut self,
+        epsilon: f32,
+        rng: &mut R,
+    ) -> Result<(T::Action, f32), EvalError> {
+        let moves = self.evaluate()?;
+        pick_for_strength(self.get_root(), moves, self.strength(), Some(epsilon), rng)
+            .ok_or(EvalError::NoMoves)
+    }
+
+    fn apply_move(&mut self, mv: T::Action) -> Result<(), T::Error>;
+    fn undo_move(&mut self) -> Result<(), T::Error>;
+    // Drops anything learned about the root, needed when scoring changes without a move
+    fn clear_search(&mut self);
+}
+
+// Everything that changes what a session's search returns, so equal descriptions of one
+// position can share results
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvalDescription {
+    pub algorithm: String,
+    pub depth: Option<u8>,
+    pub time: Option<Duration>,
+    pub cache: bool,
+    pub strength: Option<Strength>,
+    pub restriction: Option<MoveRestriction>,
+    pub tie_seed: Option<u64>,
+    // fill ratio from which the depth is capped, and the cap
+    pub endgame: Option<(f32, u8)>,
+    // Monte Carlo scoring, None ranks by visits
+    pub temperature: Option<f32>,
+    pub seed: Option<u64>,
+}
 
-    fn apply_move(&mut self, mv: T::Action) -> Result<(), String>;
-    fn undo_move(&mut self) -> Result<(), String>;
+// Thresholds and temperatures come from configuration and are never NaN
+impl Eq for EvalDescription {}
+
+impl Hash for EvalDescription {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.algorithm.hash(state);
+        self.depth.hash(state);
+        self.time.hash(state);
+        self.cache.hash(state);
+        self.strength.hash(state);
+        self.restriction.hash(state);
+        self.tie_seed.hash(state);
+        self.endgame.map(|(t, d)| (t.to_bits(), d)).hash(state);
+        self.temperature.map(f32::to_bits).hash(state);
+        self.seed.hash(state);
+    }
 }
 
 pub enum AnyEvaluator {
@@ -34,18 +372,38 @@ pub enum AnyEvaluator {
     MonteCarlo(montecarlo::MonteCarlo),
 }
 
+impl AnyEvaluator {
+    pub fn with_threads(self, threads: usize) -> Result<Self, EvalError> {
+        match self {
+            AnyEvaluator::AlphaBeta(a) => Ok(AnyEvaluator::AlphaBeta(a.with_threads(threads)?)),
+            AnyEvaluator::MonteCarlo(m) => Ok(AnyEvaluator::MonteCarlo(m)),
+        }
+    }
+}
+
 impl Evaluator for AnyEvaluator {
-    fn evaluate<T: Heuristic>(&self, root: &mut T) -> Result<Vec<(T::Action, f32)>, String> {
+    fn evaluate<T: Heuristic>(&self, root: &mut T) -> Result<Vec<(T::Action, f32)>, EvalError> {
+        match self {
+            AnyEvaluator::AlphaBeta(a) => a.evaluate(root),
+            AnyEvaluator::MonteCarlo(m) => m.evaluate(root),
+        }
+    }
+
+    fn evaluate_candidates<T: Heuristic>(
+        &self,
+        root: &mut T,
+        candidates: &[T::Action],
+    ) -> Result<Vec<(T::Action, f32)>, EvalError> {
         match self {
-            &AnyEvaluator::AlphaBeta(ref a) => a.evaluate(root),
-            &AnyEvaluator::MonteCarlo(ref m) => m.evaluate(root),
+            AnyEvaluator::AlphaBeta(a) => a.evaluate_candidates(root, candidates),
+            AnyEvaluator::MonteCarlo(m) => m.evaluate_candidates(root, candidates),
         }
     }
 
     fn is_multi_threaded(&self) -> bool {
         match self {
-            &AnyEvaluator::AlphaBeta(ref a) => a.is_multi_threaded(),
-            &AnyEvaluator::MonteCarlo(ref m) => m.is_multi_threaded(),
+            AnyEvaluator::AlphaBeta(a) => a.is_multi_threaded(),
+            AnyEvaluator::MonteCarlo(m) => m.is_multi_threaded(),
         }
     }
 }
@@ -56,15 +414,88 @@ pub enum AnyEvaluationSession<T: Heuristic> {
     MonteCarlo(montecarlo::MonteCarloSession<T>),
 }
 
+impl<T: Heuristic> AnyEvaluationSession<T> {
+    pub fn with_strength(self, strength: Strength) -> Self {
+        match self {
+            AnyEvaluationSession::AlphaBeta(a) => {
+                AnyEvaluationSession::AlphaBeta(a.with_strength(strength))
+            }
+            AnyEvaluationSession::MonteCarlo(m) => {
+                AnyEvaluationSession::MonteCarlo(m.with_strength(strength))
+            }
+        }
+    }
+
+    pub fn with_move_restriction(self, restriction: Option<MoveRestriction>) -> Self {
+        match self {
+            AnyEvaluationSession::AlphaBeta(a) => {
+                AnyEvaluationSession::AlphaBeta(a.with_move_restriction(restriction))
+            }
+            AnyEvaluationSession::MonteCarlo(m) => {
+                AnyEvaluationSession::MonteCarlo(m.with_move_restriction(restriction))
+            }
+        }
+    }
+
+    // Something to search with outside of a lock, see MonteCarloSession::detach. Alpha-beta
+    // keeps its results in the table, so a copy is enough
+    pub fn detach_search(&mut self) -> Self {
+        match self {
+            AnyEvaluationSession::AlphaBeta(a) => AnyEvaluationSession::AlphaBeta(a.clone()),
+            AnyEvaluationSession::MonteCarlo(m) => AnyEvaluationSession::MonteCarlo(m.detach()),
+        }
+    }
+
+    pub fn attach_search(&mut self, searched: Self) {
+        if let (AnyEvaluationSession::MonteCarlo(m), AnyEvaluationSession::MonteCarlo(s)) =
+            (self, searched)
+        {
+            m.attach(s);
+        }
+    }
+
+    pub fn describe(&self) -> EvalDescription {
+        match self {
+            AnyEvaluationSession::AlphaBeta(a) => EvalDescription {
+                algorithm: "alpha-beta".to_string(),
+                depth: Some(a.depth()),
+                time: a.time_budget(),
+                cache: a.has_cache(),
+                strength: a.strength(),
+                restriction: a.move_restriction(),
+                tie_seed: a.tie_seed(),
+                endgame: a.endgame(),
+                temperature: None,
+                seed: None,
+            },
+            AnyEvaluationSession::MonteCarlo(m) => EvalDescription {
+                algorithm: "monte-carlo".to_string(),
+                depth: None,
+                time: Some(m.time),
+                cache: false,
+                strength: m.strength(),
+                restriction: m.restriction,
+                tie_seed: None,
+                endgame: None,
+                temperature: match m.scoring {
+                    montecarlo::ScoreOption::Visits => None,
+                    montecarlo::ScoreOption::Temperature(t) => Some(t),
+                },
+                seed: m.seed,
+            },
+        }
+    }
+}
+
 impl<T: Heuristic> EvaluationSession<T> for AnyEvaluationSession<T> {
-    fn apply_move(&mut self, mv: T::Action) -> Result<(), String> {
+    fn apply_move(&mut self, mv: T::Action) -> Result<(), T::Error> {
         match self {
             AnyEvaluationSession::AlphaBeta(ref mut a) => a.apply_move(mv),
             AnyEvaluationSession::MonteCarlo(ref mut m) => m.apply_move(mv),
         }
     }
 
-    fn undo_move(&mut self) -> Result<(), String> {
+    fn undo_move(&mut self) -> Result<(), T::Error> {
         match self {
             AnyEvaluationSession::AlphaBeta(ref mut a) => a.undo_move(),
             AnyEvaluationSession::MonteCarlo(ref mut m) => m.undo_move(),
@@ -78,17 +509,273 @@ impl<T: Heuristic> EvaluationSession<T> for AnyEvaluationSession<T> {
         }
     }
 
-    fn evaluate(&mut self) -> Result<Vec<(T::Action, f32)>, String> {
+    fn strength(&self) -> Option<Strength> {
+        match self {
+            AnyEvaluationSession::AlphaBeta(ref a) => a.strength(),
+            AnyEvaluationSession::MonteCarlo(ref m) => m.strength(),
+        }
+    }
+
+    fn evaluate(&mut self) -> Result<Vec<(T::Action, f32)>, EvalError> {
         match self {
             AnyEvaluationSession::AlphaBeta(ref mut a) => a.evaluate(),
             AnyEvaluationSession::MonteCarlo(ref mut m) => m.evaluate(),
         }
     }
 
+    fn evaluate_candidates(
+        &mut self,
+        candidates: &[T::Action],
+    ) -> Result<Vec<(T::Action, f32)>, EvalError> {
+        match self {
+            AnyEvaluationSession::AlphaBeta(ref mut a) => a.evaluate_candidates(candidates),
+            AnyEvaluationSession::MonteCarlo(ref mut m) => m.evaluate_candidates(candidates),
+        }
+    }
+
     fn get_root(&self) -> &T {
         match self {
             AnyEvaluationSession::AlphaBeta(ref a) => a.get_root(),
             AnyEvaluationSession::MonteCarlo(ref m) => m.get_root(),
         }
     }
+
+    fn get_root_mut(&mut self) -> &mut T {
+        match self {
+            AnyEvaluationSession::AlphaBeta(ref mut a) => a.get_root_mut(),
+            AnyEvaluationSession::MonteCarlo(ref mut m) => m.get_root_mut(),
+        }
+    }
+
+    fn clear_search(&mut self) {
+        match self {
+            AnyEvaluationSession::AlphaBeta(ref mut a) => a.clear_search(),
+            AnyEvaluationSession::MonteCarlo(ref mut m) => m.clear_search(),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::hash::{DefaultHasher, Hash, Hasher};
+
+    use super::*;
+
+    // Each move picks an index and adds its value for the side that picked it, the game ends
+    // after a fixed number of moves. The best move is always the most valuable index
+    #[derive(Clone, Debug)]
+    pub(crate) struct PickGame {
+        pub values: Vec<f32>,
+        pub length: usize,
+        pub played: Vec<usize>,
+        // subtracted from the final score, like komi on a board
+        pub komi: f32,
+        // most moves ever played at once, so tests can see how far a search went
+        pub deepest: usize,
+    }
+
+    impl PickGame {
+        pub fn new(values: &[f32], length: usize) -> Self {
+            Self {
+                values: values.to_vec(),
+                length,
+                played: Vec::new(),
+                komi: 0.0,
+                deepest: 0,
+            }
+        }
+
+        fn best_value(&self) -> f32 {
+            self.values.iter().cloned().fold(0.0, f32::max)
+        }
+    }
+
+    impl Heuristic for PickGame {
+        type Action = usize;
+        type Error = String;
+
+        fn calculate_heuristic(&self) -> f32 {
+            self.played
+                .iter()
+                .enumerate()
+                .map(|(i, &mv)| match i % 2 {
+                    0 => self.values[mv],
+                    _ => -self.values[mv],
+                })
+                .sum::<f32>()
+                - self.komi
+        }
+        fn max_score(&self) -> f32 {
+            self.length as f32 * self.best_value() + self.komi.abs()
+        }
+        fn open_points(&self) -> f32 {
+            self.length.saturating_sub(self.played.len()) as f32 * self.best_value()
+        }
+        fn is_terminal(&self) -> bool {
+            self.played.len() >= self.length
+        }
+        fn is_maximizing(&self) -> bool {
+            self.played.len().is_multiple_of(2)
+        }
+        fn move_number(&self) -> usize {
+            self.played.len()
+        }
+        fn get_hash(&self) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            self.played.hash(&mut hasher);
+            hasher.finish()
+        }
+        fn moves(&self) -> impl Iterator<Item = usize> {
+            let count = match self.is_terminal() {
+                true => 0,
+                false => self.values.len(),
+            };
+            0..count
+        }
+        fn play(&mut self, mv: usize) -> Result<(), String> {
+            if self.is_terminal() || mv >= self.values.len() {
+                return Err(format!("Invalid move {mv}"));
+            }
+            self.played.push(mv);
+            self.deepest = self.deepest.max(self.played.len());
+            Ok(())
+        }
+        fn undo(&mut self) -> Result<(), String> {
+            self.played
+                .pop()
+                .map(|_| ())
+                .ok_or("No move to undo".to_string())
+        }
+    }
+
+    fn picks(strength: Strength) -> Vec<usize> {
+        let root = PickGame::new(&[0.0, 1.0, 10.0], 1);
+        let mut session =
+            alphabeta::AlphaBetaSession::new(root, 1, alphabeta::CacheOption::Disable)
+                .with_strength(strength);
+        (0..200).map(|_| session.best_move().unwrap().0).collect()
+    }
+
+    #[test]
+    fn only_weaker_strengths_miss_a_clear_best_move() {
+        assert!(picks(Strength::Beginner).iter().any(|&mv| mv != 2));
+        assert!(picks(Strength::Strong).iter().all(|&mv| mv == 2));
+    }
+
+    #[test]
+    fn sorted_evaluations_start_with_the_best_move_for_either_side() {
+        let root = PickGame::new(&[1.0, 5.0, 3.0], 2);
+        let mut session =
+            alphabeta::AlphaBetaSession::new(root, 1, alphabeta::CacheOption::Disable);
+        let order =
+            |moves: Vec<(usize, f32)>| moves.into_iter().map(|(mv, _)| mv).collect::<Vec<_>>();
+
+        assert_eq!(order(session.evaluate_sorted().unwrap()), [1, 2, 0]);
+
+        // The minimizing side wants the lowest scores first
+        session.apply_move(0).unwrap();
+        let sorted = session.evaluate_sorted().unwrap();
+        assert_eq!(order(sorted.clone()), [1, 2, 0]);
+        assert!(sorted.windows(2).all(|w| w[0].1 <= w[1].1), "{:?}", sorted);
+    }
+
+    #[test]
+    fn non_finite_scores_become_finite_and_sort_in_place() {
+        let root = PickGame::new(&[0.0; 4], 1);
+        let mut moves = [f32::NAN, f32::INFINITY, f32::NEG_INFINITY, 1.0]
+            .into_iter()
+            .enumerate()
+            .map(|(mv, score)| (mv, finite_score(score)))
+            .collect::<Vec<_>>();
+        assert!(moves.iter().all(|(_, s)| s.is_finite()), "{:?}", moves);
+        assert_eq!(moves[0].1, 0.0);
+        assert_eq!((moves[1].1, moves[2].1), (f32::MAX, f32::MIN));
+
+        sort_best_first(&root, &mut moves);
+        assert_eq!(
+            moves.iter().map(|(mv, _)| *mv).collect::<Vec<_>>(),
+            [1, 3, 0, 2]
+        );
+        assert_eq!(pick_best(&root, moves), Some((1, f32::MAX)));
+    }
+
+    #[test]
+    fn sessions_describe_their_algorithm() {
+        let root = PickGame::new(&[1.0, 2.0], 2);
+        let alpha_beta = AnyEvaluationSession::AlphaBeta(alphabeta::AlphaBetaSession::new(
+            root.clone(),
+            6,
+            alphabeta::CacheOption::Disable,
+        ));
+        let plain = EvalDescription {
+            algorithm: "alpha-beta".to_string(),
+            depth: Some(6),
+            time: None,
+            cache: false,
+            strength: None,
+            restriction: None,
+            tie_seed: None,
+            endgame: None,
+            temperature: None,
+            seed: None,
+        };
+        assert_eq!(alpha_beta.describe(), plain);
+
+        // Settings that change the result give a different description
+        let restriction = MoveRestriction { moves: 4, lines: 1 };
+        let configured = |session: alphabeta::AlphaBetaSession<PickGame>| {
+            AnyEvaluationSession::AlphaBeta(session).describe()
+        };
+        let base =
+            || alphabeta::AlphaBetaSession::new(root.clone(), 6, alphabeta::CacheOption::Disable);
+        let described = [
+            configured(base().with_move_restriction(Some(restriction))),
+            configured(base().with_tie_shuffle(Some(3))),
+            configured(base().with_endgame(0.8, 2)),
+        ];
+        assert_eq!(described[0].restriction, Some(restriction));
+        assert_eq!(described[1].tie_seed, Some(3));
+        assert_eq!(described[2].endgame, Some((0.8, 2)));
+        let distinct = described.iter().collect::<std::collections::HashSet<_>>();
+        assert_eq!(distinct.len(), 3);
+        assert!(!distinct.contains(&plain));
+
+        let time = Duration::from_millis(250);
+        let base = || montecarlo::MonteCarloSession::new(root.clone(), time);
+        let described = AnyEvaluationSession::MonteCarlo(base()).describe();
+        assert_eq!(described.algorithm, "monte-carlo");
+        assert_eq!((described.depth, described.time), (None, Some(time)));
+
+        // Scoring and seeds are part of the description, so they key cached results apart
+        let mut tempered = base();
+        tempered.scoring = montecarlo::ScoreOption::Temperature(0.5);
+        let configured = [
+            described,
+            AnyEvaluationSession::MonteCarlo(tempered).describe(),
+            AnyEvaluationSession::MonteCarlo(base().with_seed(1)).describe(),
+            AnyEvaluationSession::MonteCarlo(base().with_seed(2)).describe(),
+        ];
+        assert_eq!(configured[1].temperature, Some(0.5));
+        assert_eq!(configured[2].seed, Some(1));
+        let distinct = configured.iter().collect::<std::collections::HashSet<_>>();
+        assert_eq!(distinct.len(), 4);
+    }
+
+    #[test]
+    fn seeds_vary_among_equally_good_moves() {
+        let root = PickGame::new(&[5.0, 0.0, 5.0, 5.0], 1);
+        let moves = vec![(0, 5.0), (1, 0.0), (2, 5.0), (3, 5.0)];
+        let pick = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            pick_best_random(&root, moves.clone(), 0.0, &mut rng)
+                .unwrap()
+                .0
+        };
+
+        let picked = (0..20).map(pick).collect::<Vec<_>>();
+        assert!(picked.iter().all(|&mv| mv != 1));
+        assert!(picked.iter().any(|&mv| mv != picked[0]));
+        assert_eq!(pick(3), pick(3));
+        assert_eq!(pick_best(&root, moves.clone()), Some((0, 5.0)));
+    }
 }
@@ -1,14 +1,138 @@
 use core::f32;
-use std::time::{Duration, Instant};
+use std::{
+    cmp::Reverse,
+    time::{Duration, Instant},
+};
 
 use rand::{
     rng,
-    seq::{IndexedRandom, IteratorRandom},
+    rngs::StdRng,
+    seq::{IndexedRandom, IteratorRandom, SliceRandom},
+    Rng, SeedableRng,
 };
 
-use crate::{EvaluationSession, Evaluator, Heuristic};
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use crate::{
+    finite_score, EvalError, EvaluationSession, Evaluator, Heuristic, MoveRestriction, Strength,
+};
 
 const UCB1: f32 = 1.1;
+// UCB1 values this close are treated as tied and chosen between at random
+const TIE_EPSILON: f32 = 1e-4;
+// Subtrees kept after undoing, so replaying the same moves resumes the search
+const REDO_CAPACITY: usize = 16;
+// Only the most visited children of each node are exported
+const EXPORT_BREADTH: usize = 8;
+
+#[derive(Clone, Copy, Debug)]
+pub enum ScoreOption {
+    Visits,
+    // visits^(1/T) normalized to sum to 1, a temperature of 0 selects the most visited move
+    Temperature(f32),
+}
+
+impl ScoreOption {
+    fn scores<T: Heuristic>(&self, node: &Node<T>) -> Vec<(T::Action, f32)> {
+        self.raw_scores(node)
+            .into_iter()
+            .map(|(m, s)| (m, finite_score(s)))
+            .collect()
+    }
+
+    fn raw_scores<T: Heuristic>(&self, node: &Node<T>) -> Vec<(T::Action, f32)> {
+        // Like the heuristic, scores are reported from the maximizing side's perspective
+        let sign = if node.maximizing { 1.0 } else { -1.0 };
+        let children = match node.children.as_ref() {
+            Some(c) => c,
+            None => return Vec::new(),
+        };
+
+        let temperature = match *self {
+            ScoreOption::Visits => {
+                return children
+                    .iter()
+                    .map(|(m, n)| (*m, sign * n.visits as f32))
+                    .collect();
+            }
+            ScoreOption::Temperature(t) => t,
+        };
+
+        let max_visits = children.iter().map(|(_, n)| n.visits).max().unwrap_or(0);
+        if max_visits == 0 {
+            let uniform = 1.0 / children.len() as f32;
+            return children.iter().map(|(m, _)| (*m, sign * uniform)).collect();
+        }
+
+        // Weights are computed relative to the most visited move to keep them from overflowing
+        let weights = children
+            .iter()
+            .map(|(_, n)| {
+                if temperature <= f32::EPSILON {
+                    return if n.visits == max_visits { 1.0 } else { 0.0 };
+                }
+                if n.visits == 0 {
+                    return 0.0;
+                }
+                ((n.visits as f32 / max_visits as f32).ln() / temperature).exp()
+            })
+            .collect::<Vec<_>>();
+        let total: f32 = weights.iter().sum();
+
+        children
+            .iter()
+            .zip(weights)
+            .map(|((m, _), w)| (*m, sign * w / total))
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PlayoutOptions {
+    // only pass in rollouts once the game has no productive placements left
+    pub suppress_pass: bool,
+    // rollouts stop after this many moves and score the position reached
+    pub max_rollout_depth: Option<usize>,
+    // rollouts stop once one side is too far ahead to be caught
+    pub mercy: Option<MercyRule>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MercyRule {
+    // the margin, komi included, has to exceed this share of the points still open to play,
+    // 1.0 means all of them
+    pub threshold: f32,
+    // moves played between two checks of the margin
+    pub interval: usize,
+}
+
+impl MercyRule {
+    fn applies<T: Heuristic>(&self, game: &T, played: usize) -> bool {
+        if self.interval == 0 || !played.is_multiple_of(self.interval) {
+            return false;
+        }
+
+        game.calculate_heuristic().abs() > self.threshold * game.open_points()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct VisitSpread<A> {
+    pub mv: A,
+    pub mean: f32,
+    pub stddev: f32,
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct TreeNodeJson<A> {
+    pub mv: Option<A>,
+    pub visits: usize,
+    pub total: f32,
+    pub mean: f32,
+    pub children: Vec<TreeNodeJson<A>>,
+}
 
 #[derive(Clone)]
 struct Node<T: Heuristic> {
@@ -33,6 +157,8 @@ impl<T: Heuristic> Node<T> {
             return;
         }
 
+        // A game-ending move leaves nobody to move, so the child's side comes from the parent
+        let maximizing = game.is_maximizing();
         let moves = game.moves().collect::<Vec<_>>();
         let mut children: Vec<(T::Action, Node<T>)> = Vec::new();
         for mv in moves {
@@ -42,22 +168,71 @@ impl<T: Heuristic> Node<T> {
                 continue;
             }
 
-            children.push((mv, Self::new(game.is_maximizing())));
+            children.push((mv, Self::new(!maximizing)));
             game.undo().unwrap();
         }
 
         self.children = Some(children);
     }
 
+    // A root whose children are only the legal moves among the candidates
+    fn with_candidates(game: &mut T, candidates: &[T::Action]) -> Option<Self> {
+        let mut node = Self::new(game.is_maximizing());
+        node.expand(game);
+        if let Some(children) = node.children.as_mut() {
+            children.retain(|(mv, _)| candidates.contains(mv));
+        }
+
+        node.children
+            .as_ref()
+            .is_some_and(|c| !c.is_empty())
+            .then_some(node)
+    }
+
+    fn export(&self, mv: Option<T::Action>, depth: usize) -> TreeNodeJson<T::Action> {
+        let mut children = match (depth, self.children.as_ref()) {
+            (1.., Some(c)) => c.iter().collect::<Vec<_>>(),
+            _ => Vec::new(),
+        };
+        children.sort_by_key(|(_, n)| Reverse(n.visits));
+        children.truncate(EXPORT_BREADTH);
+
+        TreeNodeJson {
+            mv,
+            visits: self.visits,
+            total: self.total,
+            mean: if self.visits == 0 {
+                0.0
+            } else {
+                self.total / self.visits as f32
+            },
+            children: children
+                .into_iter()
+                .map(|(m, n)| n.export(Some(*m), depth - 1))
+                .collect(),
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        1 + self
+            .children
+            .as_ref()
+            .map_or(0, |c| c.iter().map(|(_, n)| n.count()).sum())
+    }
+
     pub fn ucb1(&self, parent_visits: usize) -> f32 {
+        if self.visits == 0 {
+            return f32::MAX;
+        }
+
         let exploration = (2.0 * (parent_visits as f32).ln() / self.visits as f32).sqrt() * UCB1;
         let signed_score = if self.maximizing {
-            self.total * -1.0
+            -self.total
         } else {
             self.total
         };
         let exploitation = signed_score / self.visits as f32;
-        let exploitation = 1.0 / (1.0 + (-0.3 * exploitation).exp());
+        let exploitation = (exploitation + 1.0) / 2.0;
 
         if exploration.is_infinite() || exploitation.is_infinite() {
             return f32::MAX;
@@ -66,53 +241,76 @@ impl<T: Heuristic> Node<T> {
         exploitation + exploration
     }
 
-    pub fn simulate(game: &mut T) -> f32 {
-        if game.is_terminal() {
-            return game.calculate_heuristic();
-        }
+    fn terminal_value(game: &T) -> f32 {
+        (game.calculate_heuristic() / game.max_score()).clamp(-1.0, 1.0)
+    }
 
-        let moves = game.moves().collect::<Vec<_>>();
-        loop {
-            let &chosen = moves.choose(&mut rng()).unwrap();
-            let result = game.play(chosen);
+    pub fn simulate<R: Rng + ?Sized>(game: &mut T, options: &PlayoutOptions, rng: &mut R) -> f32 {
+        let mut played = 0;
+        while !game.is_terminal() && options.max_rollout_depth.is_none_or(|d| played < d) {
+            let mut candidates = if options.suppress_pass {
+                game.playout_moves().collect::<Vec<_>>()
+            } else {
+                game.moves().collect::<Vec<_>>()
+            };
+            // Passing out a settled position leaves the score unchanged
+            if candidates.len() <= 1 && game.is_decided() {
+                break;
+            }
+            candidates.shuffle(rng);
+
+            if !candidates.into_iter().any(|mv| game.play(mv).is_ok()) {
+                let moves = game.moves().collect::<Vec<_>>();
+                loop {
+                    let &chosen = moves.choose(rng).unwrap();
+                    let result = game.play(chosen);
+
+                    if result.is_ok() {
+                        break;
+                    }
+                }
+            }
+            played += 1;
 
-            if result.is_ok() {
+            if options.mercy.is_some_and(|m| m.applies(game, played)) {
                 break;
             }
         }
 
-        let value = Self::simulate(game);
-        game.undo().unwrap();
+        let value = Self::terminal_value(game);
+        for _ in 0..played {
+            game.undo().unwrap();
+        }
 
         value
     }
 
-    pub fn max_child(&mut self) -> (T::Action, &mut Node<T>) {
-        let mut cur_value = f32::MIN;
-        let mut cur_max: Vec<(T::Action, &mut Node<T>)> = Vec::new();
-
+    pub fn max_child<R: Rng + ?Sized>(&mut self, rng: &mut R) -> (T::Action, &mut Node<T>) {
+        let parent_visits = self.visits;
         let children = self.children.as_mut().unwrap();
-        for (mv, node) in children.iter_mut() {
-            let value = node.ucb1(self.visits);
-
-            if cur_value > value {
-                continue;
-            }
-
-            if cur_value < value {
-                cur_value = value;
-                cur_max.clear();
-            }
-
-            cur_max.push((*mv, node));
-        }
-
-        cur_max.into_iter().choose(&mut rng()).unwrap()
+        let values = children
+            .iter()
+            .map(|(_, node)| node.ucb1(parent_visits))
+            .collect::<Vec<_>>();
+        let max = values.iter().copied().fold(f32::MIN, f32::max);
+
+        children
+            .iter_mut()
+            .zip(values)
+            .filter(|(_, value)| max - value <= TIE_EPSILON)
+            .map(|((mv, node), _)| (*mv, node))
+            .choose(rng)
+            .unwrap()
     }
 
-    pub fn backpropagate(&mut self, game: &mut T) -> f32 {
+    pub fn backpropagate<R: Rng + ?Sized>(
+        &mut self,
+        game: &mut T,
+        options: &PlayoutOptions,
+        rng: &mut R,
+    ) -> f32 {
         if game.is_terminal() {
-            let value = game.calculate_heuristic();
+            let value = Self::terminal_value(game);
 
             self.total += value;
             self.visits += 1;
@@ -125,10 +323,10 @@ impl<T: Heuristic> Node<T> {
         }
 
         if self.children.is_some() {
-            let (mv, child) = self.max_child();
+            let (mv, child) = self.max_child(rng);
 
             game.play(mv).unwrap();
-            let value = child.backpropagate(game);
+            let value = child.backpropagate(game, options, rng);
             game.undo().unwrap();
 
             self.total += value;
@@ -137,7 +335,7 @@ impl<T: Heuristic> Node<T> {
             return value;
         }
 
-        let value = Self::simulate(game);
+        let value = Self::simulate(game, options, rng);
         self.total += value;
         self.visits += 1;
 
@@ -147,32 +345,84 @@ impl<T: Heuristic> Node<T> {
 
 pub struct MonteCarlo {
     pub time: Duration,
+    pub scoring: ScoreOption,
+    pub playout: PlayoutOptions,
+    pub restriction: Option<MoveRestriction>,
 }
 
 impl MonteCarlo {
     pub fn new(time: Duration) -> Self {
-        Self { time }
+        Self {
+            time,
+            scoring: ScoreOption::Visits,
+            playout: PlayoutOptions::default(),
+            restriction: None,
+        }
+    }
+
+    pub fn with_move_restriction(mut self, restriction: Option<MoveRestriction>) -> Self {
+        self.restriction = restriction;
+        self
+    }
+
+    pub fn with_strength(mut self, strength: Strength) -> Self {
+        self.time = strength.time(self.time);
+        if let Some(t) = strength.temperature() {
+            self.scoring = ScoreOption::Temperature(t);
+        }
+        self
+    }
+}
+
+impl MonteCarlo {
+    // A fixed number of iterations instead of a time limit, reproducible with a seeded rng
+    pub fn evaluate_iterations<T: Heuristic, R: Rng + ?Sized>(
+        &self,
+        game: &mut T,
+        iterations: usize,
+        rng: &mut R,
+    ) -> Vec<(T::Action, f32)> {
+        let mut root: Node<T> = Node::new(game.is_maximizing());
+        for _ in 0..iterations {
+            root.backpropagate(game, &self.playout, rng);
+        }
+
+        self.scoring.scores(&root)
     }
 }
 
 impl Evaluator for MonteCarlo {
-    fn evaluate<T: Heuristic>(&self, game: &mut T) -> Result<Vec<(T::Action, f32)>, String> {
+    fn evaluate<T: Heuristic>(&self, game: &mut T) -> Result<Vec<(T::Action, f32)>, EvalError> {
+        if let Some(moves) = self.restriction.and_then(|r| r.root_moves(game)) {
+            return self.evaluate_candidates(game, &moves);
+        }
+
         let mut root: Node<T> = Node::new(game.is_maximizing());
 
         let start = Instant::now();
         while Instant::now() - start < self.time {
-            root.backpropagate(game);
+            root.backpropagate(game, &self.playout, &mut rng());
         }
 
-        Ok(root
-            .children
-            .unwrap()
-            .into_iter()
-            .map(|(m, n)| {
-                let sign = if root.maximizing { 1.0 } else { -1.0 };
-                (m, sign * n.visits as f32)
-            })
-            .collect())
+        Ok(self.scoring.scores(&root))
+    }
+
+    // The whole search budget goes into the candidates, illegal ones are left out
+    fn evaluate_candidates<T: Heuristic>(
+        &self,
+        game: &mut T,
+        candidates: &[T::Action],
+    ) -> Result<Vec<(T::Action, f32)>, EvalError> {
+        let Some(mut root) = Node::with_candidates(game, candidates) else {
+            return Ok(Vec::new());
+        };
+
+        let start = Instant::now();
+        while Instant::now() - start < self.time {
+            root.backpropagate(game, &self.playout, &mut rng());
+        }
+
+        Ok(self.scoring.scores(&root))
     }
 
     fn is_multi_threaded(&self) -> bool {
@@ -183,18 +433,143 @@ impl Evaluator for MonteCarlo {
 #[derive(Clone)]
 pub struct MonteCarloSession<T: Heuristic> {
     node: Node<T>,
+    played: Vec<T::Action>,
+    undone: Vec<(T::Action, Node<T>)>,
+    // Every search of the session draws from this, so a seeded session repeats its playouts
+    rng: Box<StdRng>,
 
     pub root: T,
     pub time: Duration,
+    pub scoring: ScoreOption,
+    pub playout: PlayoutOptions,
+    pub strength: Option<Strength>,
+    pub restriction: Option<MoveRestriction>,
+    // the seed of the rng, None when it was seeded from the thread rng
+    pub seed: Option<u64>,
 }
 
 impl<T: Heuristic> MonteCarloSession<T> {
     pub fn new(root: T, time: Duration) -> Self {
         Self {
             time,
+            scoring: ScoreOption::Visits,
+            playout: PlayoutOptions::default(),
             node: Node::new(root.is_maximizing()),
+            played: Vec::new(),
+            undone: Vec::new(),
+            rng: Box::new(StdRng::from_rng(&mut rng())),
             root,
+            strength: None,
+            restriction: None,
+            seed: None,
+        }
+    }
+
+    pub fn with_move_restriction(mut self, restriction: Option<MoveRestriction>) -> Self {
+        self.restriction = restriction;
+        self
+    }
+
+    pub fn with_strength(mut self, strength: Strength) -> Self {
+        self.time = strength.time(self.time);
+        if let Some(t) = strength.temperature() {
+            self.scoring = ScoreOption::Temperature(t);
+        }
+        self.strength = Some(strength);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = Box::new(StdRng::seed_from_u64(seed));
+        self.seed = Some(seed);
+        self
+    }
+
+    // Mean value of the searched root from -1 to 1, positive favors the maximizing side.
+    // None until the root has been visited
+    pub fn root_value(&self) -> Option<f32> {
+        (self.node.visits > 0).then(|| self.node.total / self.node.visits as f32)
+    }
+
+    // A copy to search with that takes the tree along, this session keeps an empty one at the
+    // same root until attach puts the searched tree back
+    pub fn detach(&mut self) -> Self {
+        let node = std::mem::replace(&mut self.node, Node::new(self.root.is_maximizing()));
+        Self {
+            node,
+            played: Vec::new(),
+            undone: Vec::new(),
+            rng: self.rng.clone(),
+            root: self.root.clone(),
+            time: self.time,
+            scoring: self.scoring,
+            playout: self.playout,
+            strength: self.strength,
+            restriction: self.restriction,
+            seed: self.seed,
+        }
+    }
+
+    // Only valid while this session is still at the root the copy was detached from
+    pub fn attach(&mut self, searched: Self) {
+        self.node = searched.node;
+        self.rng = searched.rng;
+    }
+
+    pub fn tree_size(&self) -> usize {
+        self.node.count()
+    }
+
+    pub fn export_tree(&self, max_depth: usize) -> TreeNodeJson<T::Action> {
+        self.node.export(None, max_depth)
+    }
+
+    // Runs independent searches from fresh trees, leaving the session tree untouched
+    pub fn evaluate_spread(
+        &mut self,
+        runs: usize,
+    ) -> Result<Vec<VisitSpread<T::Action>>, EvalError> {
+        if runs == 0 {
+            return Err(EvalError::InvalidParam(
+                "At least one run is required".to_string(),
+            ));
+        }
+
+        let mut shares: Vec<(T::Action, Vec<f32>)> = Vec::new();
+        for _ in 0..runs {
+            let mut node: Node<T> = Node::new(self.root.is_maximizing());
+            let start = Instant::now();
+            while Instant::now() - start < self.time {
+                node.backpropagate(&mut self.root, &self.playout, &mut self.rng);
+            }
+
+            let children = match node.children {
+                Some(c) => c,
+                None => continue,
+            };
+            let total = children.iter().map(|(_, n)| n.visits).sum::<usize>().max(1);
+            for (mv, child) in children {
+                let share = child.visits as f32 / total as f32;
+                match shares.iter_mut().find(|(m, _)| *m == mv) {
+                    Some((_, s)) => s.push(share),
+                    None => shares.push((mv, vec![share])),
+                }
+            }
         }
+
+        Ok(shares
+            .into_iter()
+            .map(|(mv, mut s)| {
+                s.resize(runs, 0.0);
+                let mean = s.iter().sum::<f32>() / runs as f32;
+                let variance = s.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / runs as f32;
+                VisitSpread {
+                    mv,
+                    mean,
+                    stddev: variance.sqrt(),
+                }
+            })
+            .collect())
     }
 }
 
@@ -203,52 +578,300 @@ impl<T: Heuristic> EvaluationSession<T> for MonteCarloSession<T> {
         false
     }
 
-    fn apply_move(&mut self, mv: T::Action) -> Result<(), String> {
+    fn strength(&self) -> Option<Strength> {
+        self.strength
+    }
+
+    fn apply_move(&mut self, mv: T::Action) -> Result<(), T::Error> {
         self.root.play(mv)?;
+        self.played.push(mv);
+
+        // The top of the redo stack is always a child of the current root
+        match self.undone.pop() {
+            Some((undone, node)) if undone == mv => {
+                self.node = node;
+                return Ok(());
+            }
+            _ => self.undone.clear(),
+        }
 
         if self.node.children.is_none() {
             self.node = Node::new(self.root.is_maximizing());
             return Ok(());
         }
 
+        // The root has already moved, so an unexpanded move starts a fresh tree
         let children = self.node.children.take().unwrap();
-
-        let new_node = children
+        self.node = children
             .into_iter()
             .find(|a| a.0 == mv)
-            .ok_or("move not in children".to_string())?;
-        self.node = new_node.1;
+            .map_or_else(|| Node::new(self.root.is_maximizing()), |a| a.1);
 
         Ok(())
     }
 
-    fn undo_move(&mut self) -> Result<(), String> {
+    fn undo_move(&mut self) -> Result<(), T::Error> {
         self.root.undo()?;
-        self.node = Node::new(self.root.is_maximizing());
+        let node = std::mem::replace(&mut self.node, Node::new(self.root.is_maximizing()));
+
+        match self.played.pop() {
+            Some(mv) => {
+                if self.undone.len() >= REDO_CAPACITY {
+                    self.undone.remove(0);
+                }
+                self.undone.push((mv, node));
+            }
+            None => self.undone.clear(),
+        }
 
         Ok(())
     }
 
-    fn evaluate(&mut self) -> Result<Vec<(<T as Heuristic>::Action, f32)>, String> {
+    fn evaluate(&mut self) -> Result<Vec<(<T as Heuristic>::Action, f32)>, EvalError> {
+        // Children outside the restriction are dropped from the root, the moves below keep theirs
+        if let Some(moves) = self.restriction.and_then(|r| r.root_moves(&self.root)) {
+            self.node.expand(&mut self.root);
+            if let Some(children) = self.node.children.as_mut() {
+                children.retain(|(mv, _)| moves.contains(mv));
+            }
+        }
+
         let start = Instant::now();
         while Instant::now() - start < self.time {
-            self.node.backpropagate(&mut self.root);
+            self.node
+                .backpropagate(&mut self.root, &self.playout, &mut self.rng);
         }
 
-        Ok(self
-            .node
-            .children
-            .as_ref()
-            .unwrap()
-            .into_iter()
-            .map(|(m, n)| {
-                let sign = if self.node.maximizing { 1.0 } else { -1.0 };
-                (*m, sign * n.visits as f32)
-            })
-            .collect())
+        Ok(self.scoring.scores(&self.node))
+    }
+
+    // Searched in a separate tree, a restricted root would skew later evaluations
+    fn evaluate_candidates(
+        &mut self,
+        candidates: &[T::Action],
+    ) -> Result<Vec<(T::Action, f32)>, EvalError> {
+        let evaluator = MonteCarlo {
+            time: self.time,
+            scoring: self.scoring,
+            playout: self.playout,
+            restriction: None,
+        };
+        evaluator.evaluate_candidates(&mut self.root, candidates)
     }
 
     fn get_root(&self) -> &T {
         &self.root
     }
+
+    fn get_root_mut(&mut self) -> &mut T {
+        &mut self.root
+    }
+
+    fn clear_search(&mut self) {
+        self.node = Node::new(self.root.is_maximizing());
+        self.undone.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::PickGame;
+
+    fn session(game: PickGame, millis: u64) -> MonteCarloSession<PickGame> {
+        MonteCarloSession::new(game, Duration::from_millis(millis)).with_seed(7)
+    }
+
+    fn visited(visits: &[usize]) -> Node<PickGame> {
+        let mut root = Node::new(true);
+        root.visits = visits.iter().sum();
+        root.children = Some(
+            visits
+                .iter()
+                .enumerate()
+                .map(|(mv, &v)| {
+                    let mut child = Node::new(false);
+                    child.visits = v;
+                    (mv, child)
+                })
+                .collect(),
+        );
+        root
+    }
+
+    #[test]
+    fn temperature_scores_are_a_distribution_sharpening_as_it_drops() {
+        let root = visited(&[10, 5, 1]);
+        let top_share = |t: f32| {
+            let scores = ScoreOption::Temperature(t).scores(&root);
+            let total = scores.iter().map(|(_, s)| s).sum::<f32>();
+            assert!((total - 1.0).abs() < 1e-5, "sum {} at {}", total, t);
+            scores[0].1
+        };
+
+        assert!((top_share(1.0) - 10.0 / 16.0).abs() < 1e-5);
+        assert!(top_share(0.5) > top_share(1.0));
+        assert_eq!(top_share(0.0), 1.0);
+        assert_eq!(ScoreOption::Visits.scores(&root)[1], (1, 5.0));
+    }
+
+    #[test]
+    fn near_equal_children_are_picked_at_random() {
+        let mut root = visited(&[4, 4, 4]);
+        // Differences far below the tie epsilon, as float sums of equal playouts end up
+        for (i, (_, child)) in root.children.as_mut().unwrap().iter_mut().enumerate() {
+            child.total = 2.0 + i as f32 * 1e-6;
+        }
+        let mut pick = |seed| root.max_child(&mut StdRng::seed_from_u64(seed)).0;
+
+        let picked = (0..20).map(&mut pick).collect::<Vec<_>>();
+        assert!(picked.iter().any(|&mv| mv != picked[0]), "{:?}", picked);
+        assert_eq!(pick(4), pick(4));
+    }
+
+    #[test]
+    fn large_komi_still_tells_the_moves_apart() {
+        let mut game = PickGame::new(&[0.0, 1.0, 10.0], 1);
+        game.komi = 50.0;
+        let mut visits = session(game, 50).evaluate().unwrap();
+        visits.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        assert_eq!(visits[0].0, 2);
+        assert!(
+            visits[0].1 > 2.0 * visits[1].1,
+            "{} vs {} visits",
+            visits[0].1,
+            visits[1].1
+        );
+    }
+
+    #[test]
+    fn rollouts_stop_at_the_depth_cap() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let options = |cap| PlayoutOptions {
+            max_rollout_depth: cap,
+            ..Default::default()
+        };
+        let mut endless = PickGame::new(&[1.0, 2.0], usize::MAX);
+        Node::simulate(&mut endless, &options(Some(10)), &mut rng);
+        assert_eq!(endless.deepest, 10);
+        assert!(endless.played.is_empty());
+
+        // A tighter cap plays fewer moves per rollout, and the best move still comes out on top
+        let mut deepest = |cap| {
+            let mut game = PickGame::new(&[0.0, 1.0, 10.0], 40);
+            let search = MonteCarlo {
+                playout: options(cap),
+                ..MonteCarlo::new(Duration::ZERO)
+            };
+            let mut scores = search.evaluate_iterations(&mut game, 300, &mut rng);
+            scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+            assert_eq!(scores[0].0, 2, "cap {:?}", cap);
+            game.deepest
+        };
+        let (capped, uncapped) = (deepest(Some(4)), deepest(None));
+        assert!(capped < uncapped, "{} vs {}", capped, uncapped);
+        assert_eq!(uncapped, 40);
+    }
+
+    #[test]
+    fn exported_visits_add_up_to_the_childrens() {
+        // Every visit after the first playout at a node goes to one of its children
+        fn check(node: &TreeNodeJson<usize>) {
+            if node.children.is_empty() {
+                return;
+            }
+            let children = node.children.iter().map(|c| c.visits).sum::<usize>();
+            assert_eq!(node.visits, children + 1, "at {:?}", node.mv);
+            node.children.iter().for_each(check);
+        }
+
+        let mut session = session(PickGame::new(&[0.0, 1.0, 3.0], 3), 20);
+        session.evaluate().unwrap();
+        let tree = session.export_tree(usize::MAX);
+        assert!(tree.visits > 1);
+        assert_eq!(tree.children.len(), 3);
+        check(&tree);
+
+        let shallow = session.export_tree(1);
+        assert!(shallow.children.iter().all(|c| c.children.is_empty()));
+        assert_eq!(shallow.visits, tree.visits);
+    }
+
+    #[test]
+    fn replaying_an_undone_move_keeps_its_subtree() {
+        let mut session = session(PickGame::new(&[0.0, 1.0, 10.0], 3), 20);
+        session.evaluate().unwrap();
+        session.apply_move(2).unwrap();
+        let (visits, size) = (session.node.visits, session.tree_size());
+        assert!(visits > 0);
+
+        session.undo_move().unwrap();
+        assert_eq!(session.node.visits, 0);
+        session.apply_move(2).unwrap();
+        assert_eq!(session.node.visits, visits);
+        assert_eq!(session.tree_size(), size);
+
+        // Another move in between drops the cached subtree
+        session.undo_move().unwrap();
+        session.apply_move(1).unwrap();
+        session.undo_move().unwrap();
+        session.apply_move(2).unwrap();
+        assert_eq!(session.node.visits, 0);
+    }
+
+    #[test]
+    fn mercy_ends_hopeless_rollouts_with_the_same_winner() {
+        // No sequence of moves makes up for the komi, though it is below the maximum score
+        let hopeless = || {
+            let mut game = PickGame::new(&[0.0, 1.0, 2.0], 40);
+            game.komi = 50.0;
+            game
+        };
+        let rollout = |mercy| {
+            let options = PlayoutOptions {
+                mercy,
+                ..Default::default()
+            };
+            let mut game = hopeless();
+            let value = Node::simulate(&mut game, &options, &mut StdRng::seed_from_u64(3));
+            (value, game.deepest)
+        };
+
+        let (full, played) = rollout(None);
+        let (called, cut) = rollout(Some(MercyRule {
+            threshold: 0.5,
+            interval: 2,
+        }));
+        assert_eq!(played, 40);
+        assert_eq!(cut, 2);
+        assert!(full < 0.0 && called < 0.0, "{} and {}", full, called);
+
+        // A close game keeps going
+        let mut close = PickGame::new(&[0.0, 1.0, 2.0], 40);
+        let options = PlayoutOptions {
+            mercy: Some(MercyRule {
+                threshold: 0.5,
+                interval: 2,
+            }),
+            ..Default::default()
+        };
+        Node::simulate(&mut close, &options, &mut StdRng::seed_from_u64(3));
+        assert!(close.deepest > 20, "{}", close.deepest);
+    }
+
+    #[test]
+    fn spread_is_tight_on_a_clear_best_move() {
+        let mut session = session(PickGame::new(&[0.0, 0.0, 10.0], 1), 50);
+        let spread = session.evaluate_spread(3).unwrap();
+        let best = spread.iter().find(|s| s.mv == 2).unwrap();
+        assert!(best.mean > 0.5, "mean share {}", best.mean);
+        assert!(best.stddev < 0.1, "stddev {}", best.stddev);
+    }
+
+    #[test]
+    fn spread_needs_a_run() {
+        let mut session = session(PickGame::new(&[1.0], 1), 1);
+        assert!(session.evaluate_spread(0).is_err());
+    }
 }
@@ -0,0 +1,210 @@
+use std::{ffi::OsString, path::PathBuf};
+
+use clap::{error::ErrorKind, CommandFactory, Parser, ValueEnum};
+use evaluation::alphabeta::TranspositionTable;
+
+const DEFAULT_DEPTH: usize = 6;
+const DEFAULT_SECONDS: usize = 4;
+const DEFAULT_THREADS: usize = 4;
+const DEFAULT_CACHE_BYTES: usize = 300_000_000;
+// The largest board in the game, the cache budget is given for this size
+const LARGEST_SIZE: usize = 19;
+
+// Scales with the number of points, so a 5x5 table is 25/361 of the given capacity
+pub fn capacity_for_size(capacity: usize, size: u8) -> usize {
+    let points = (size as usize).pow(2);
+    (capacity.saturating_mul(points) / LARGEST_SIZE.pow(2)).max(1)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Algorithm {
+    AlphaBeta,
+    MonteCarlo,
+}
+
+// The command line as given, ServerConfig fills in the defaults that depend on the algorithm
+#[derive(Parser, Debug)]
+#[command(
+    name = "server",
+    about = "Evaluates IPvGO boards over HTTP on port 5151"
+)]
+struct LaunchArgs {
+    /// Search algorithm of the sessions
+    #[arg(value_enum, ignore_case = true)]
+    algorithm: Algorithm,
+    /// Search depth for alpha-beta, seconds per evaluation for monte-carlo [default: 6 or 4]
+    #[arg(value_parser = parse_count)]
+    param: Option<usize>,
+    /// Threads for batch evaluations and parallel searches
+    #[arg(long, default_value_t = DEFAULT_THREADS, value_parser = parse_count)]
+    threads: usize,
+    /// Memory of the shared alpha-beta table of a 19x19 board, smaller boards get a share by area
+    #[arg(long, default_value_t = DEFAULT_CACHE_BYTES, value_parser = parse_count)]
+    cache_bytes: usize,
+    /// Origin allowed to make cross-origin requests
+    #[arg(long, default_value = "*")]
+    cors_origin: String,
+    /// Directory the alpha-beta tables are loaded from when first used and saved to on shutdown
+    #[arg(long)]
+    persistence_dir: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServerConfig {
+    pub algorithm: Algorithm,
+    // search depth for alpha-beta, seconds per evaluation for monte-carlo
+    pub param: usize,
+    pub threads: usize,
+    // entries in the shared alpha-beta table of a 19x19 board, from the cache budget in bytes
+    pub table_capacity: usize,
+    pub cors_origin: String,
+    pub persistence_dir: Option<PathBuf>,
+}
+
+fn parse_count(value: &str) -> Result<usize, String> {
+    value
+        .parse::<usize>()
+        .ok()
+        .filter(|v| *v > 0)
+        .ok_or("expected a positive number".to_string())
+}
+
+impl ServerConfig {
+    pub fn table_capacity_for(&self, size: u8) -> usize {
+        capacity_for_size(self.table_capacity, size)
+    }
+
+    pub fn table_path(&self, size: u8) -> Option<PathBuf> {
+        let dir = self.persistence_dir.as_ref()?;
+        Some(dir.join(format!("table-{}.bin", size)))
+    }
+
+    // The errors carry the usage, clap prints them and exits with Error::exit
+    pub fn parse<I, T>(arg_list: I) -> Result<ServerConfig, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let args = LaunchArgs::try_parse_from(arg_list)?;
+        let invalid =
+            |message: String| LaunchArgs::command().error(ErrorKind::ValueValidation, message);
+
+        let param = args.param.unwrap_or(match args.algorithm {
+            Algorithm::AlphaBeta => DEFAULT_DEPTH,
+            Algorithm::MonteCarlo => DEFAULT_SECONDS,
+        });
+        if args.algorithm == Algorithm::AlphaBeta && param > u8::MAX as usize {
+            return Err(invalid(format!(
+                "Depth {} is too large, at most {}",
+                param,
+                u8::MAX
+            )));
+        }
+        if let Some(dir) = args.persistence_dir.as_ref().filter(|d| d.exists()) {
+            if !dir.is_dir() {
+                return Err(invalid(format!("'{}' is not a directory", dir.display())));
+            }
+        }
+
+        let table_capacity = TranspositionTable::capacity_for_bytes(args.cache_bytes);
+        if table_capacity == 0 {
+            return Err(invalid(format!(
+                "{} bytes don't hold a single table entry",
+                args.cache_bytes
+            )));
+        }
+
+        Ok(ServerConfig {
+            algorithm: args.algorithm,
+            param,
+            threads: args.threads,
+            table_capacity,
+            cors_origin: args.cors_origin,
+            persistence_dir: args.persistence_dir,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(arg_list: &str) -> Result<ServerConfig, clap::Error> {
+        ServerConfig::parse(arg_list.split_whitespace())
+    }
+
+    #[test]
+    fn defaults_depend_on_the_algorithm() {
+        let config = parse("server alpha-beta").unwrap();
+        assert_eq!(
+            config,
+            ServerConfig {
+                algorithm: Algorithm::AlphaBeta,
+                param: DEFAULT_DEPTH,
+                threads: DEFAULT_THREADS,
+                table_capacity: TranspositionTable::capacity_for_bytes(DEFAULT_CACHE_BYTES),
+                cors_origin: "*".to_string(),
+                persistence_dir: None,
+            }
+        );
+
+        let config = parse("server Monte-Carlo").unwrap();
+        assert_eq!(config.algorithm, Algorithm::MonteCarlo);
+        assert_eq!(config.param, DEFAULT_SECONDS);
+        assert_eq!(config.table_path(5), None);
+    }
+
+    #[test]
+    fn every_option_can_be_overridden() {
+        let config = parse(
+            "server alpha-beta 3 --threads 2 --cache-bytes 1000000 \
+             --cors-origin http://localhost --persistence-dir /tmp/tables",
+        )
+        .unwrap();
+        assert_eq!(config.param, 3);
+        assert_eq!(config.threads, 2);
+        assert_eq!(
+            config.table_capacity,
+            TranspositionTable::capacity_for_bytes(1_000_000)
+        );
+        assert_eq!(config.cors_origin, "http://localhost");
+        assert_eq!(
+            config.table_path(5),
+            Some(PathBuf::from("/tmp/tables/table-5.bin"))
+        );
+    }
+
+    #[test]
+    fn invalid_options_are_errors() {
+        assert_eq!(
+            parse("server").unwrap_err().kind(),
+            ErrorKind::MissingRequiredArgument
+        );
+        assert_eq!(
+            parse("server minimax").unwrap_err().kind(),
+            ErrorKind::InvalidValue
+        );
+        assert_eq!(
+            parse("server alpha-beta 0").unwrap_err().kind(),
+            ErrorKind::ValueValidation
+        );
+        assert_eq!(
+            parse("server alpha-beta 256").unwrap_err().kind(),
+            ErrorKind::ValueValidation
+        );
+        assert_eq!(
+            parse("server alpha-beta --cache-bytes 1")
+                .unwrap_err()
+                .kind(),
+            ErrorKind::ValueValidation
+        );
+        assert_eq!(
+            parse("server alpha-beta --table 10").unwrap_err().kind(),
+            ErrorKind::UnknownArgument
+        );
+        assert_eq!(
+            parse("server alpha-beta 3 4").unwrap_err().kind(),
+            ErrorKind::UnknownArgument
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use board::error::BoardError;
+use evaluation::EvalError;
+use rocket::{
+    http::Status,
+    response::{self, Responder},
+    serde::{json::Json, Serialize},
+    Request, Response,
+};
+
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: Status,
+    pub code: &'static str,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct ApiErrorBody<'a> {
+    code: &'a str,
+    message: &'a str,
+}
+
+impl ApiError {
+    pub fn new(status: Status, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn session_not_found(message: String) -> Self {
+        Self::new(Status::NotFound, "session_not_found", message)
+    }
+
+    pub fn illegal_move(message: String) -> Self {
+        Self::new(Status::NotAcceptable, "illegal_move", message)
+    }
+
+    pub fn bad_request(message: String) -> Self {
+        Self::new(Status::BadRequest, "bad_request", message)
+    }
+
+    // The request needs a game that is still going
+    pub fn game_over(message: String) -> Self {
+        Self::new(Status::Conflict, "game_over", message)
+    }
+
+    pub fn payload_too_large(message: String) -> Self {
+        Self::new(Status::PayloadTooLarge, "payload_too_large", message)
+    }
+
+    pub fn internal(message: String) -> Self {
+        Self::new(Status::InternalServerError, "internal_error", message)
+    }
+}
+
+// Illegal moves keep the illegal_move code, the validate route gives the exact reason. A move
+// after the game ended is a game_over conflict like on every other move route
+impl From<BoardError> for ApiError {
+    fn from(error: BoardError) -> Self {
+        match error {
+            BoardError::InvalidRep(message) => Self::bad_request(message),
+            BoardError::GameOver(_) => Self::game_over(error.to_string()),
+            BoardError::InvalidKomi(_) | BoardError::GameNotOver => {
+                Self::bad_request(error.to_string())
+            }
+            BoardError::NoMoveToUndo => {
+                Self::new(Status::NotAcceptable, "invalid_undo", error.to_string())
+            }
+            _ => Self::illegal_move(error.to_string()),
+        }
+    }
+}
+
+impl From<EvalError> for ApiError {
+    fn from(error: EvalError) -> Self {
+        match error {
+            EvalError::InvalidParam(message) => Self::bad_request(message),
+            EvalError::NoMoves => Self::game_over(error.to_string()),
+            EvalError::ThreadPool(_) | EvalError::Io(_) | EvalError::InvalidTable(_) => {
+                Self::internal(error.to_string())
+            }
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        println!("{} ({}): {}", self.status, self.code, self.message);
+
+        let body = Json(ApiErrorBody {
+            code: self.code,
+            message: &self.message,
+        });
+        Response::build_from(body.respond_to(req)?)
+            .status(self.status)
+            .ok()
+    }
+}
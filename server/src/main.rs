@@ -1,33 +1,101 @@
-use std::{env::args, time::Duration};
+use std::{
+    collections::HashMap,
+    env::{args_os, var},
+    fs,
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
+};
 
-use board::{Board, Move};
+use board::{error::BoardError, Board, Move, Tile, Turn};
+use config::{capacity_for_size, Algorithm, ServerConfig};
+use error::ApiError;
 use evaluation::{
-    alphabeta::{AlphaBetaSession, CacheOption},
-    montecarlo::MonteCarloSession,
-    AnyEvaluationSession, EvaluationSession,
+    alphabeta::{AlphaBeta, AlphaBetaSession, CacheOption, TranspositionTable},
+    finite_score,
+    montecarlo::{MonteCarlo, MonteCarloSession},
+    pick_best, pick_for_strength, sort_best_first, AnyEvaluationSession, AnyEvaluator, EvalError,
+    EvaluationSession, Evaluator, Heuristic,
 };
+use rand::{rng, rngs::StdRng, SeedableRng};
+use rayon::prelude::*;
 use requests::{
-    SessionBoardState, SessionCreateData, SessionEvaluationData, SessionIdentifier,
-    SessionListData, SessionMoveRequest, SessionMoveResponse, SessionUndoResponse,
+    AnalyzeRequest, AnalyzeResponse, BatchEvaluationRequest, MoveValidation, SessionBoardState,
+    SessionCacheData, SessionCandidatesRequest, SessionCreateData, SessionEvaluationData,
+    SessionEvaluatorData, SessionHistoryData, SessionHistoryEntry, SessionIdentifier,
+    SessionKomiRequest, SessionKomiResponse, SessionListData, SessionMoveRequest,
+    SessionMoveResponse, SessionResetResponse, SessionReviewEntry, SessionScoreData,
+    SessionUndoResponse, SessionWhatIfResponse, SessionWinrateData, ValidationData,
 };
 use rocket::{
-    fairing::{Fairing, Info, Kind},
+    data::{self, Capped, Data, FromData, Limits, ToByteUnit},
+    fairing::{AdHoc, Fairing, Info, Kind},
     figment::Figment,
     http::{Header, Method, Status},
     response::content::RawHtml,
-    serde::json::Json,
+    serde::{json::Json, DeserializeOwned},
     tokio::{task::spawn_blocking, time::Instant},
     Request, Response, State,
 };
-use store::SessionStore;
+use store::{BoardData, Session, SessionStore};
 
 #[macro_use]
 extern crate rocket;
 
+mod config;
+mod error;
 mod requests;
 mod store;
 
-pub struct CORS;
+const DEFAULT_BATCH_LIMIT: usize = 64;
+const DEFAULT_RESULT_CACHE: usize = 1024;
+const BATCH_CACHE_CAPACITY: usize = 100_000;
+const REVIEW_DEPTH: usize = 3;
+const REVIEW_SECONDS: usize = 1;
+// Budgets a request can ask for, so a single request can't hold a worker indefinitely
+const MAX_REQUEST_DEPTH: u8 = 12;
+const MAX_REQUEST_SECONDS: usize = 60;
+// JSON bodies get this much per board, enough for the rep of a 100x100 board
+const BOARD_BODY_LIMIT: usize = 16 * 1024;
+// SGF and replay bodies
+const TEXT_BODY_LIMIT: usize = 1024 * 1024;
+
+pub struct BatchLimit(pub usize);
+
+pub struct CORS(pub String);
+
+// JSON read under the "batch" limit, the "json" limit of every other route fits one board
+pub struct BatchJson<T>(pub T);
+
+#[rocket::async_trait]
+impl<'r, T: DeserializeOwned> FromData<'r> for BatchJson<T> {
+    type Error = String;
+
+    async fn from_data(req: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let limit = req.limits().get("batch").unwrap_or(Limits::JSON);
+        let text = match data.open(limit).into_string().await {
+            Ok(text) if text.is_complete() => text.into_inner(),
+            Ok(_) => {
+                let message = format!("The request body is larger than {} bytes", limit);
+                return data::Outcome::Error((Status::PayloadTooLarge, message));
+            }
+            Err(e) => return data::Outcome::Error((Status::BadRequest, e.to_string())),
+        };
+
+        match rocket::serde::json::from_str(&text) {
+            Ok(value) => data::Outcome::Success(BatchJson(value)),
+            Err(e) => data::Outcome::Error((Status::UnprocessableEntity, e.to_string())),
+        }
+    }
+}
+
+// Bodies over the limit are never read into memory. Only the batch route gets room for more
+// than one board
+fn body_limits(batch_limit: usize) -> Limits {
+    Limits::default()
+        .limit("json", BOARD_BODY_LIMIT.bytes())
+        .limit("batch", (BOARD_BODY_LIMIT * batch_limit.max(1)).bytes())
+        .limit("string", TEXT_BODY_LIMIT.bytes())
+}
 
 #[rocket::async_trait]
 impl Fairing for CORS {
@@ -42,7 +110,7 @@ impl Fairing for CORS {
         if req.method() == Method::Options {
             res.set_status(Status::NoContent);
         }
-        res.set_header(Header::new("Access-Control-Allow-Origin", "*"));
+        res.set_header(Header::new("Access-Control-Allow-Origin", self.0.clone()));
         res.set_header(Header::new(
             "Access-Control-Allow-Methods",
             "GET, POST, PUT, DELETE",
@@ -54,18 +122,173 @@ impl Fairing for CORS {
     }
 }
 
+// The table is sized for the board like the session tables, BATCH_CACHE_CAPACITY being the 19x19 one
+fn create_evaluator(
+    algorithm: &str,
+    param: Option<usize>,
+    size: u8,
+) -> Result<AnyEvaluator, EvalError> {
+    match algorithm.to_lowercase().trim() {
+        "alpha-beta" => {
+            let depth = param.unwrap_or(6);
+            let depth = u8::try_from(depth)
+                .ok()
+                .filter(|d| (1..=MAX_REQUEST_DEPTH).contains(d))
+                .ok_or(EvalError::InvalidParam(format!(
+                    "Invalid depth {}, expected 1 to {}",
+                    depth, MAX_REQUEST_DEPTH
+                )))?;
+            Ok(AnyEvaluator::AlphaBeta(AlphaBeta::new(
+                depth,
+                CacheOption::Capacity(capacity_for_size(BATCH_CACHE_CAPACITY, size)),
+            )))
+        }
+        "monte-carlo" => {
+            let seconds = param.unwrap_or(4);
+            if !(1..=MAX_REQUEST_SECONDS).contains(&seconds) {
+                return Err(EvalError::InvalidParam(format!(
+                    "Invalid time {}, expected 1 to {} seconds",
+                    seconds, MAX_REQUEST_SECONDS
+                )));
+            }
+            Ok(AnyEvaluator::MonteCarlo(MonteCarlo::new(
+                Duration::from_secs(seconds as u64),
+            )))
+        }
+        any => Err(EvalError::InvalidParam(format!(
+            "Invalid algorithm '{}'",
+            any
+        ))),
+    }
+}
+
+// Bypasses the session caches, which only hold evaluations of every move
+#[post("/session/<id>/evaluation", format = "json", data = "<data>")]
+async fn post_session_evaluation(
+    id: usize,
+    data: Json<SessionCandidatesRequest>,
+    store: &State<SessionStore>,
+) -> Result<Json<SessionEvaluationData>, ApiError> {
+    let session = store
+        .get_session(&id)
+        .map_err(ApiError::session_not_found)?;
+    let board = session.board().clone();
+
+    let legal = board.moves().collect::<Vec<_>>();
+    let mut candidates = Vec::new();
+    for mv in data.into_inner().candidates {
+        let place = board.to_place_move(mv);
+        if !legal.contains(&place) {
+            return Err(ApiError::illegal_move(format!(
+                "Candidate {:?} is not a legal move",
+                mv
+            )));
+        }
+        if !candidates.contains(&place) {
+            candidates.push(place);
+        }
+    }
+    if candidates.is_empty() {
+        return Err(ApiError::bad_request(
+            "At least one candidate is required".to_string(),
+        ));
+    }
+
+    let start = Instant::now();
+    let mut evaluation_session = session.evaluation_session;
+    let result = spawn_blocking(move || evaluation_session.evaluate_candidates(&candidates))
+        .await
+        .map_err(|e| ApiError::internal(e.to_string()))??;
+
+    Ok(Json(SessionEvaluationData {
+        time: Instant::now() - start,
+        moves: to_coord_moves(&board, result),
+    }))
+}
+
+fn to_coord_moves(board: &Board, moves: Vec<(Move, f32)>) -> Vec<(Move, f32)> {
+    moves
+        .into_iter()
+        .map(|m| (board.to_coords_move(m.0), m.1))
+        .collect()
+}
+
 #[get("/")]
 fn index() -> RawHtml<&'static str> {
     RawHtml("<h1>Hello World!</h1>")
 }
 
-#[get("/session/<id>/state")]
+// ?legal=true adds a legality map of every point for the side to move
+#[get("/session/<id>/state?<legal>")]
 fn get_session_state(
+    id: usize,
+    legal: Option<bool>,
+    store: &State<SessionStore>,
+) -> Result<Json<SessionBoardState>, ApiError> {
+    let session = store
+        .get_session(&id)
+        .map_err(ApiError::session_not_found)?;
+    let state = SessionBoardState::new(session.board());
+    Ok(Json(match legal {
+        Some(true) => state.with_legal(session.board()),
+        _ => state,
+    }))
+}
+
+#[get("/session/<id>/evaluator")]
+fn get_session_evaluator(
+    id: usize,
+    store: &State<SessionStore>,
+) -> Result<Json<SessionEvaluatorData>, ApiError> {
+    let session = store
+        .get_session(&id)
+        .map_err(ApiError::session_not_found)?;
+    Ok(Json(session.evaluation_session.describe().into()))
+}
+
+#[get("/session/<id>/cache")]
+fn get_session_cache(
     id: usize,
     store: &State<SessionStore>,
-) -> Result<Json<SessionBoardState>, Status> {
-    let session = store.get_session(&id).map_err(|_| Status::NotFound)?;
-    Ok(Json(SessionBoardState::new(&session.board())))
+) -> Result<Json<SessionCacheData>, ApiError> {
+    let session = store
+        .get_session(&id)
+        .map_err(ApiError::session_not_found)?;
+
+    Ok(Json(match &session.evaluation_session {
+        AnyEvaluationSession::AlphaBeta(a) => {
+            let stats = a.table_stats().unwrap_or_default();
+            let lookups = stats.hits + stats.misses;
+            SessionCacheData {
+                stored_states: Some(stats.stored),
+                capacity: Some(stats.capacity),
+                fill_ratio: Some(stats.stored as f32 / stats.capacity.max(1) as f32),
+                hit_rate: Some(stats.hits as f32 / lookups.max(1) as f32),
+                tree_nodes: None,
+            }
+        }
+        AnyEvaluationSession::MonteCarlo(m) => SessionCacheData {
+            stored_states: None,
+            capacity: None,
+            fill_ratio: None,
+            hit_rate: None,
+            tree_nodes: Some(m.tree_size()),
+        },
+    }))
+}
+
+#[get("/session/<id>/score")]
+fn get_session_score(
+    id: usize,
+    store: &State<SessionStore>,
+) -> Result<Json<SessionScoreData>, ApiError> {
+    let session = store
+        .get_session(&id)
+        .map_err(ApiError::session_not_found)?;
+    Ok(Json(SessionScoreData {
+        current_score: finite_score(session.board().calculate_heuristic()),
+        liberties: session.board().liberty_summary(),
+    }))
 }
 
 #[put("/session/<id>/move", format = "json", data = "<data>")]
@@ -73,100 +296,475 @@ fn put_session_move(
     id: usize,
     data: Json<SessionMoveRequest>,
     store: &State<SessionStore>,
-) -> Result<Json<SessionMoveResponse>, Status> {
-    let mut session = store.get_session(&id).map_err(|_| Status::NotFound)?;
+) -> Result<Json<SessionMoveResponse>, ApiError> {
     let mv = data.into_inner().mv;
+    let state = store
+        .modify_session(&id, |session| {
+            session.apply_move(mv)?;
+            Ok::<_, BoardError>(SessionBoardState::new(session.board()))
+        })
+        .map_err(ApiError::session_not_found)??;
 
-    session.apply_move(mv).map_err(|e| {
-        println!("Move provided is not valid: {}", e);
-        Status::NotAcceptable
-    })?;
-    store.update_session(id, session.clone());
+    Ok(Json(SessionMoveResponse::new(mv, state)))
+}
 
-    Ok(Json(SessionMoveResponse::new(
-        mv,
-        SessionBoardState::new(&session.board()),
-    )))
+// The session plays its own move, picked from its evaluation with its strength. With ties any
+// move within that much of the best can be played, reproducibly with a seed
+#[put("/session/<id>/engine-move?<ties>&<seed>")]
+async fn put_session_engine_move(
+    id: usize,
+    ties: Option<f32>,
+    seed: Option<u64>,
+    store: &State<SessionStore>,
+) -> Result<Json<SessionMoveResponse>, ApiError> {
+    if ties.is_some_and(|t| t.is_nan() || t < 0.0) {
+        return Err(ApiError::bad_request(
+            "Ties has to be a non-negative number".to_string(),
+        ));
+    }
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_rng(&mut rng()),
+    };
+
+    let evaluated = store
+        .session_handle(&id)
+        .map_err(ApiError::session_not_found)?
+        .lock()
+        .unwrap()
+        .board()
+        .clone();
+    if evaluated.turn == Turn::None {
+        return Err(ApiError::game_over("The game is over".to_string()));
+    }
+    let (_, moves) = session_evaluation(id, store).await?;
+
+    let (mv, state) = store
+        .modify_session(&id, |session| {
+            if !session.is_at(&evaluated) {
+                return Err(ApiError::illegal_move(
+                    "The session moved on during the evaluation".to_string(),
+                ));
+            }
+            let strength = session.evaluation_session.strength();
+            let (mv, _) = pick_for_strength(session.board(), moves, strength, ties, &mut rng)
+                .ok_or(EvalError::NoMoves)?;
+            session.apply_move(mv)?;
+            Ok((mv, SessionBoardState::new(session.board())))
+        })
+        .map_err(ApiError::session_not_found)??;
+
+    Ok(Json(SessionMoveResponse::new(mv, state)))
 }
 
 #[put("/session/<id>/undo")]
 fn put_session_undo(
     id: usize,
     store: &State<SessionStore>,
-) -> Result<Json<SessionUndoResponse>, Status> {
-    let mut session = store.get_session(&id).map_err(|_| Status::NotFound)?;
+) -> Result<Json<SessionUndoResponse>, ApiError> {
+    let state = store
+        .modify_session(&id, |session| {
+            session.undo_move()?;
+            Ok::<_, BoardError>(SessionBoardState::new(session.board()))
+        })
+        .map_err(ApiError::session_not_found)??;
 
-    session.undo_move().map_err(|e| {
-        println!("Undo is not valid: {}", e);
-        Status::NotAcceptable
-    })?;
-    store.update_session(id, session.clone());
+    Ok(Json(SessionUndoResponse { state }))
+}
 
-    Ok(Json(SessionUndoResponse {
-        state: SessionBoardState::new(&session.board()),
-    }))
+#[put("/session/<id>/komi", format = "json", data = "<data>")]
+fn put_session_komi(
+    id: usize,
+    data: Json<SessionKomiRequest>,
+    store: &State<SessionStore>,
+) -> Result<Json<SessionKomiResponse>, ApiError> {
+    let komi = data.into_inner().komi;
+    let response = store
+        .modify_session(&id, |session| {
+            session.set_komi(komi)?;
+            Ok::<_, BoardError>(SessionKomiResponse {
+                state: SessionBoardState::new(session.board()),
+                current_score: finite_score(session.board().calculate_heuristic()),
+            })
+        })
+        .map_err(ApiError::session_not_found)??;
+
+    Ok(Json(response))
+}
+
+#[put("/session/<id>/reset")]
+fn put_session_reset(
+    id: usize,
+    store: &State<SessionStore>,
+) -> Result<Json<SessionResetResponse>, ApiError> {
+    let state = store
+        .modify_session(&id, |session| {
+            session.reset()?;
+            Ok::<_, BoardError>(SessionBoardState::new(session.board()))
+        })
+        .map_err(ApiError::session_not_found)?
+        .map_err(|e| ApiError::internal(e.to_string()))?;
+
+    Ok(Json(SessionResetResponse { state }))
+}
+
+#[get("/session/<id>/history")]
+fn get_session_history(
+    id: usize,
+    store: &State<SessionStore>,
+) -> Result<Json<SessionHistoryData>, ApiError> {
+    let session = store
+        .get_session(&id)
+        .map_err(ApiError::session_not_found)?;
+    let board = session.board();
+
+    let moves = board
+        .history
+        .iter()
+        .enumerate()
+        .map(|(i, h)| SessionHistoryEntry {
+            mv: board.to_coords_move(h.action),
+            metadata: session.move_metadata.get(i).cloned(),
+        })
+        .collect();
+
+    Ok(Json(SessionHistoryData { moves }))
+}
+
+// Every position gets a capped search, since a full game review multiplies the cost
+#[get("/session/<id>/review?<param>")]
+async fn get_session_review(
+    id: usize,
+    param: Option<usize>,
+    store: &State<SessionStore>,
+) -> Result<Json<Vec<SessionReviewEntry>>, ApiError> {
+    let session = store
+        .get_session(&id)
+        .map_err(ApiError::session_not_found)?;
+    let algorithm = session.evaluation_session.describe().algorithm;
+    let param = param.or(match algorithm.as_str() {
+        "alpha-beta" => Some(REVIEW_DEPTH),
+        _ => Some(REVIEW_SECONDS),
+    });
+    let mut board = session.board().clone();
+    let evaluator = create_evaluator(&algorithm, param, board.size)?;
+
+    let played = board.history.iter().map(|h| h.action).collect::<Vec<_>>();
+    while !board.history.is_empty() {
+        board
+            .undo_move()
+            .map_err(|e| ApiError::internal(e.to_string()))?;
+    }
+
+    let result = spawn_blocking(move || {
+        let mut entries = Vec::new();
+        for (i, mv) in played.into_iter().enumerate() {
+            let moves = evaluator.evaluate(&mut board)?;
+            let (best, score) = pick_best(&board, moves).ok_or("No moves to evaluate")?;
+            entries.push(SessionReviewEntry {
+                move_number: i + 1,
+                played: board.to_coords_move(mv),
+                best: board.to_coords_move(best),
+                score,
+            });
+            board.apply_move(mv).map_err(|e| e.to_string())?;
+        }
+        Ok::<_, String>(entries)
+    })
+    .await
+    .map_err(|e| ApiError::internal(e.to_string()))?;
+
+    Ok(Json(result.map_err(ApiError::internal)?))
+}
+
+// The evaluation of the session's current position, from the session or the shared results when
+// possible. Concurrent requests share one search
+async fn session_evaluation(
+    id: usize,
+    store: &SessionStore,
+) -> Result<(Duration, Vec<(Move, f32)>), ApiError> {
+    // A running evaluation is joined before the session is touched. Joining never starts a
+    // search, if the one running was dropped the request goes on as if there was none
+    if let Some((len, hash, pending)) = store.in_flight(id) {
+        if let Ok(Ok(result)) = pending.get_or_try_init(|| async { Err(()) }).await {
+            let handle = store
+                .session_handle(&id)
+                .map_err(ApiError::session_not_found)?;
+            let session = handle.lock().unwrap();
+            if (
+                session.board().history.len(),
+                session.board().compute_board_hash(),
+            ) == (len, hash)
+            {
+                return Ok(result.clone());
+            }
+        }
+    }
+
+    let session = store
+        .get_session(&id)
+        .map_err(ApiError::session_not_found)?;
+    let board = session.board().clone();
+
+    if let Some(cache) = session.evaluation_cache {
+        return Ok(cache);
+    }
+
+    // Sessions on the same position with the same evaluator share their results
+    let key = (
+        board.get_hash(),
+        board.history_digest(),
+        session.evaluation_session.describe(),
+    );
+    if let Some(result) = store.results.lock().unwrap().get(&key) {
+        store.update_evaluation_cache(id, &board, result.clone());
+        return Ok(result.clone());
+    }
+
+    let pending = store.pending_evaluation(id, &board);
+    let result = pending
+        .get_or_init(|| async {
+            let handle = store.session_handle(&id)?;
+            let evaluated = board.clone();
+            let mut fallback = session.evaluation_session;
+            // A copy is searched instead if the session moved on while this waited
+            let (duration, moves) = spawn_blocking(move || {
+                let stored = Session::search_in_place(&handle, &evaluated);
+                stored.unwrap_or_else(|| {
+                    let start = Instant::now();
+                    let moves = fallback.evaluate()?;
+                    Ok((start.elapsed(), to_coord_moves(&evaluated, moves)))
+                })
+            })
+            .await
+            .map_err(|e| e.to_string())??;
+
+            store
+                .results
+                .lock()
+                .unwrap()
+                .insert(key, (duration, moves.clone()));
+            Ok((duration, moves))
+        })
+        .await
+        .clone();
+    store.finish_evaluation(id, &pending);
+    result.map_err(ApiError::internal)
 }
 
 #[get("/session/<id>/evaluation")]
 async fn get_session_evaluation(
     id: usize,
     store: &State<SessionStore>,
-) -> Result<Json<SessionEvaluationData>, Status> {
-    let mut session = store.get_session(&id).map_err(|_| Status::NotFound)?;
+) -> Result<Json<SessionEvaluationData>, ApiError> {
+    let (time, moves) = session_evaluation(id, store).await?;
+    Ok(Json(SessionEvaluationData { time, moves }))
+}
+
+// Alpha-beta gives the minimax value of the best move. Monte Carlo scores moves by visits, so its
+// value comes from the root of the session's own tree, searched in place if it hasn't been yet
+#[get("/session/<id>/winrate")]
+async fn get_session_winrate(
+    id: usize,
+    store: &State<SessionStore>,
+) -> Result<Json<SessionWinrateData>, ApiError> {
+    let session = store
+        .get_session(&id)
+        .map_err(ApiError::session_not_found)?;
     let board = session.board().clone();
+    let max_score = Heuristic::max_score(&board);
 
-    if let Some(cache) = session.evaluation_cache {
-        return Ok(Json(SessionEvaluationData {
-            time: cache.0,
-            moves: cache.1,
-        }));
+    let score = if board.is_terminal() {
+        board.calculate_heuristic()
+    } else if let AnyEvaluationSession::MonteCarlo(mc) = &session.evaluation_session {
+        let value = match mc.root_value() {
+            Some(value) => value,
+            None => {
+                // Searched directly, a cached evaluation would return without visiting the root
+                let handle = store
+                    .session_handle(&id)
+                    .map_err(ApiError::session_not_found)?;
+                let searched = board.clone();
+                spawn_blocking(move || Session::search_in_place(&handle, &searched))
+                    .await
+                    .map_err(|e| ApiError::internal(e.to_string()))?
+                    .ok_or(ApiError::internal(
+                        "The session moved on during the evaluation".to_string(),
+                    ))?
+                    .map_err(ApiError::internal)?;
+                let session = store
+                    .get_session(&id)
+                    .map_err(ApiError::session_not_found)?;
+                match &session.evaluation_session {
+                    AnyEvaluationSession::MonteCarlo(mc) if session.is_at(&board) => {
+                        mc.root_value().ok_or(ApiError::internal(
+                            "The search didn't visit the position".to_string(),
+                        ))?
+                    }
+                    _ => {
+                        return Err(ApiError::internal(
+                            "The session moved on during the evaluation".to_string(),
+                        ))
+                    }
+                }
+            }
+        };
+        value * max_score
+    } else {
+        let (_, moves) = session_evaluation(id, store).await?;
+        let board = session.board();
+        pick_best(board, moves)
+            .ok_or(ApiError::internal("No moves to evaluate".to_string()))?
+            .1
+    };
+
+    let score = finite_score(score);
+    let winner_hint = match (score, session.board().maximizing) {
+        (0.0, _) => Turn::None,
+        (s, Tile::White) if s > 0.0 => Turn::White,
+        (s, _) if s > 0.0 => Turn::Black,
+        (_, Tile::White) => Turn::Black,
+        _ => Turn::White,
+    };
+    Ok(Json(SessionWinrateData {
+        score,
+        winner_hint,
+        confidence: (score.abs() / max_score).min(1.0),
+    }))
+}
+
+#[post("/session/<id>/whatif?<turn>", format = "json", data = "<data>")]
+async fn post_session_whatif(
+    id: usize,
+    turn: Option<&str>,
+    data: Json<SessionMoveRequest>,
+    store: &State<SessionStore>,
+) -> Result<Json<SessionWhatIfResponse>, ApiError> {
+    let mut session = store
+        .get_session(&id)
+        .map_err(ApiError::session_not_found)?;
+    let mv = data.into_inner().mv;
+
+    // Lets the opponent play the hypothetical move instead, the stored session is untouched
+    if let Some(turn) = turn {
+        let turn = Turn::from_str(turn)
+            .ok_or_else(|| ApiError::bad_request(format!("Invalid turn '{}'", turn)))?;
+        let board = session.board().with_turn(turn)?;
+        *session.evaluation_session.get_root_mut() = board;
+        session.evaluation_session.clear_search();
     }
 
+    session.apply_move(mv)?;
+    let board = session.board().clone();
+
     let start = Instant::now();
     let result = spawn_blocking(move || session.evaluation_session.evaluate())
         .await
-        .map_err(|_| Status::InternalServerError)?;
+        .map_err(|e| ApiError::internal(e.to_string()))?;
     let duration = Instant::now() - start;
 
-    let moves = result
-        .map_err(|_| Status::InternalServerError)?
+    let moves = to_coord_moves(&board, result?);
+
+    Ok(Json(SessionWhatIfResponse {
+        mv,
+        state: SessionBoardState::new(&board),
+        evaluation: SessionEvaluationData {
+            time: duration,
+            moves,
+        },
+    }))
+}
+
+#[post("/evaluate", format = "json", data = "<data>")]
+async fn post_evaluate(
+    data: BatchJson<BatchEvaluationRequest>,
+    limit: &State<BatchLimit>,
+) -> Result<Json<Vec<SessionEvaluationData>>, ApiError> {
+    let request = data.0;
+    if request.boards.len() > limit.0 {
+        return Err(ApiError::new(
+            Status::PayloadTooLarge,
+            "batch_too_large",
+            format!(
+                "Batch of {} boards exceeds the limit of {}",
+                request.boards.len(),
+                limit.0
+            ),
+        ));
+    }
+
+    let jobs = request
+        .boards
         .into_iter()
-        .map(|m| {
-            (
-                match m.0 {
-                    Move::Place(p) => Move::Coords(board.to_coords(p)),
-                    a => a,
-                },
-                m.1,
-            )
+        .map(|b| {
+            let board = BoardData::from(b)
+                .to_board()
+                .map_err(|e| ApiError::bad_request(e.to_string()))?;
+            let evaluator = create_evaluator(&request.algorithm, request.param, board.size)?;
+            Ok((board, evaluator))
         })
-        .collect::<Vec<_>>();
-    session.evaluation_cache = Some((duration, moves.clone()));
+        .collect::<Result<Vec<_>, ApiError>>()?;
 
-    Ok(Json(SessionEvaluationData {
-        time: duration,
-        moves,
+    let results = spawn_blocking(move || {
+        jobs.into_par_iter()
+            .map(|(mut board, evaluator)| {
+                let start = std::time::Instant::now();
+                let moves = evaluator.evaluate(&mut board)?;
+                Ok(SessionEvaluationData {
+                    time: start.elapsed(),
+                    moves: to_coord_moves(&board, moves),
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()
+    })
+    .await
+    .map_err(|e| ApiError::internal(e.to_string()))?
+    .map_err(ApiError::internal)?;
+
+    Ok(Json(results))
+}
+
+#[post("/analyze", format = "json", data = "<data>")]
+async fn post_analyze(data: Json<AnalyzeRequest>) -> Result<Json<AnalyzeResponse>, ApiError> {
+    let request = data.into_inner();
+    let board = BoardData::from(request.board).to_board()?;
+    let alpha_beta = create_evaluator("alpha-beta", request.depth, board.size)?;
+    let monte_carlo = create_evaluator("monte-carlo", request.time, board.size)?;
+
+    let run = move |evaluator: AnyEvaluator| {
+        let mut board = board.clone();
+        let start = std::time::Instant::now();
+        let mut moves = evaluator.evaluate(&mut board)?;
+        let time = start.elapsed();
+
+        sort_best_first(&board, &mut moves);
+        Ok::<_, String>(SessionEvaluationData {
+            time,
+            moves: to_coord_moves(&board, moves),
+        })
+    };
+
+    let (alpha_beta, monte_carlo) =
+        spawn_blocking(move || rayon::join(|| run(alpha_beta), || run(monte_carlo)))
+            .await
+            .map_err(|e| ApiError::internal(e.to_string()))?;
+
+    Ok(Json(AnalyzeResponse {
+        alpha_beta: alpha_beta.map_err(ApiError::internal)?,
+        monte_carlo: monte_carlo.map_err(ApiError::internal)?,
     }))
 }
 
 #[get("/session/<id>/error")]
-fn get_session_error(id: usize, store: &State<SessionStore>) -> Result<String, Status> {
-    let session = store.get_session(&id).map_err(|_| Status::NotFound)?;
+fn get_session_error(id: usize, store: &State<SessionStore>) -> Result<String, ApiError> {
+    let session = store
+        .get_session(&id)
+        .map_err(ApiError::session_not_found)?;
     let mut out = String::new();
 
     let board = session.board();
-    out += format!("Requested error information:\n").as_str();
-    out += board
-        .get_rep()
-        .char_indices()
-        .fold(String::new(), |mut a, (i, c)| {
-            if i > 0 && (i % board.size as usize) == 0 {
-                a.push('\n');
-            }
-            a.push(c);
-            a
-        })
-        .as_str();
+    out += "Requested error information:\n";
+    out += board.render_grid().as_str();
     out.push('\n');
     out.push('\n');
     for (i, c) in board.chains.iter().enumerate() {
@@ -190,96 +788,812 @@ fn get_session_error(id: usize, store: &State<SessionStore>) -> Result<String, S
 fn post_session(
     data: Json<SessionCreateData>,
     store: &State<SessionStore>,
-) -> Result<Json<SessionIdentifier>, Status> {
+) -> Result<Json<SessionIdentifier>, ApiError> {
     let creation_data = data.into_inner();
-    let created = store
-        .create_new_session(&creation_data.into())
-        .map_err(|_| Status::BadRequest)?;
+    let created = store.create_new_session(&creation_data.into())?;
     Ok(Json(created))
 }
 
+// Text cut off at the limit is rejected instead of parsed
+fn complete_text(data: Capped<String>) -> Result<String, ApiError> {
+    if !data.is_complete() {
+        return Err(ApiError::payload_too_large(format!(
+            "The request body is larger than {} bytes",
+            TEXT_BODY_LIMIT
+        )));
+    }
+    Ok(data.into_inner())
+}
+
+// Raw SGF text, the whole main line is replayed so undo and history work as usual
+#[post("/session/sgf", data = "<data>")]
+fn post_session_sgf(
+    data: Capped<String>,
+    store: &State<SessionStore>,
+) -> Result<Json<SessionIdentifier>, ApiError> {
+    let data = complete_text(data)?;
+    let board = Board::from_sgf(&data).map_err(ApiError::bad_request)?;
+    Ok(Json(store.create_session_from_board(board)))
+}
+
+// The reason codes for the errors apply_move can give
+fn illegal_reason(error: &BoardError) -> &'static str {
+    match error {
+        BoardError::OutOfBounds(_) => "out_of_bounds",
+        BoardError::Occupied(_) => "occupied",
+        BoardError::Suicide(_) => "suicide",
+        BoardError::Repetition => "repetition",
+        BoardError::WrongTurn => "wrong_turn",
+        BoardError::GameOver(_) => "game_over",
+        _ => "illegal",
+    }
+}
+
+// Raw SGF text or a replay, moves are checked in order and the report ends at the first illegal one
+#[post("/validate", data = "<data>")]
+fn post_validate(data: Capped<String>) -> Result<Json<ValidationData>, ApiError> {
+    let data = complete_text(data)?;
+    let (mut board, moves) = match data.trim_start().starts_with('(') {
+        true => Board::parse_sgf(&data),
+        false => Board::parse_replay(&data),
+    }
+    .map_err(ApiError::bad_request)?;
+
+    let mut checked = Vec::with_capacity(moves.len());
+    for (index, (color, mv)) in moves.into_iter().enumerate() {
+        let error = board.apply_move_as(color, mv).err();
+        checked.push(MoveValidation {
+            index,
+            mv: board.to_coords_move(mv),
+            legal: error.is_none(),
+            reason: error.as_ref().map(illegal_reason),
+            message: error.map(|e| e.to_string()),
+        });
+        if checked.last().is_some_and(|c| !c.legal) {
+            break;
+        }
+    }
+
+    Ok(Json(ValidationData {
+        valid: checked.iter().all(|c| c.legal),
+        moves: checked,
+    }))
+}
+
 #[get("/session")]
 fn get_session_list(store: &State<SessionStore>) -> Json<SessionListData> {
     let handle = store.sessions.lock().unwrap();
-    let sessions = handle.keys().map(|k| k.clone()).collect::<Vec<_>>();
+    let sessions = handle.keys().copied().collect::<Vec<_>>();
 
     Json(SessionListData { sessions })
 }
 
 #[delete("/session/<id>")]
-fn delete_session(id: usize, store: &State<SessionStore>) -> Status {
-    match store.delete_session(&id) {
-        Ok(_) => Status::Ok,
-        Err(_) => Status::NotFound,
-    }
+fn delete_session(id: usize, store: &State<SessionStore>) -> Result<Status, ApiError> {
+    store
+        .delete_session(&id)
+        .map_err(ApiError::session_not_found)?;
+    Ok(Status::Ok)
 }
 
 #[catch(404)]
-fn not_found() -> RawHtml<&'static str> {
-    RawHtml("<h1>Not found!</h1>")
+fn not_found(req: &Request) -> ApiError {
+    ApiError::new(
+        Status::NotFound,
+        "not_found",
+        format!("No route for '{}'", req.uri()),
+    )
 }
 
-#[launch]
-fn rocket() -> _ {
-    let arg_list = args().collect::<Vec<_>>();
-    if arg_list.len() < 2 {
-        panic!("No algorithm provided. Got {:?}", arg_list);
-    }
+#[catch(413)]
+fn payload_too_large(req: &Request) -> ApiError {
+    ApiError::payload_too_large(format!("The request body for '{}' is too large", req.uri()))
+}
+
+#[catch(default)]
+fn default_catcher(status: Status, _req: &Request) -> ApiError {
+    ApiError::new(status, "request_failed", status.reason_lossy())
+}
 
-    let param: Option<usize> = if arg_list.len() == 3 {
-        let res = arg_list[2].parse::<usize>();
+type SharedTables = Arc<Mutex<HashMap<u8, Arc<RwLock<TranspositionTable>>>>>;
 
-        if res.is_err() {
-            println!(
-                "Param for algorithm '{}' is not valid, using default!",
-                arg_list[1].to_lowercase().trim()
+// The saved table of the persistence directory if there is one, a table that can't be read is
+// started over instead of failing the session
+fn open_table(config: &ServerConfig, size: u8) -> TranspositionTable {
+    let capacity = config.table_capacity_for(size);
+    match config.table_path(size).filter(|p| p.exists()) {
+        Some(path) => TranspositionTable::load(&path, capacity).unwrap_or_else(|e| {
+            eprintln!(
+                "Starting an empty table, {} is unusable: {}",
+                path.display(),
+                e
             );
-        }
+            TranspositionTable::new(capacity)
+        }),
+        None => TranspositionTable::new(capacity),
+    }
+}
 
-        res.ok()
-    } else {
-        None
+fn save_tables(config: &ServerConfig, tables: &SharedTables) -> Result<(), String> {
+    let Some(dir) = config.persistence_dir.as_ref() else {
+        return Ok(());
     };
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    for (size, table) in tables.lock().unwrap().iter() {
+        let path = config.table_path(*size).unwrap();
+        table.read().unwrap().save(path)?;
+    }
+    Ok(())
+}
 
-    let session_fn = move |b: Board| -> AnyEvaluationSession<Board> {
-        match arg_list[1].to_lowercase().trim() {
-            "alpha-beta" => AnyEvaluationSession::AlphaBeta(AlphaBetaSession::new(
-                b,
-                param.unwrap_or(6) as u8,
-                CacheOption::Capacity(300_000_000),
-            )),
-            "monte-carlo" => AnyEvaluationSession::MonteCarlo(MonteCarloSession::new(
+// Alpha-beta sessions on the same board size share one table, sized for that board and only
+// opened by the first session on it
+fn session_factory(
+    config: ServerConfig,
+    tables: SharedTables,
+) -> impl Fn(Board) -> AnyEvaluationSession<Board> + Send + Sync + 'static {
+    move |b: Board| match config.algorithm {
+        Algorithm::AlphaBeta => {
+            let table = tables
+                .lock()
+                .unwrap()
+                .entry(b.size)
+                .or_insert_with(|| Arc::new(RwLock::new(open_table(&config, b.size))))
+                .clone();
+            AnyEvaluationSession::AlphaBeta(AlphaBetaSession::new(
                 b,
-                Duration::from_secs(param.unwrap_or(4) as u64),
-            )),
-            any => panic!("Invalid algorithm '{}'", any),
+                config.param as u8,
+                CacheOption::Shared(table),
+            ))
         }
-    };
+        Algorithm::MonteCarlo => AnyEvaluationSession::MonteCarlo(MonteCarloSession::new(
+            b,
+            Duration::from_secs(config.param as u64),
+        )),
+    }
+}
+
+#[launch]
+fn rocket() -> _ {
+    let config = ServerConfig::parse(args_os()).unwrap_or_else(|e| e.exit());
+
+    let tables = SharedTables::default();
+    let session_fn = session_factory(config.clone(), tables.clone());
 
     rayon::ThreadPoolBuilder::new()
-        .num_threads(4)
+        .num_threads(config.threads)
         .build_global()
         .unwrap();
 
+    let batch_limit = var("BATCH_LIMIT")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_BATCH_LIMIT);
+    let result_cache = var("RESULT_CACHE")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_RESULT_CACHE);
+
     let cfg = Figment::from(rocket::Config::default())
         .merge(("log_level", "off"))
-        .merge(("port", 5151));
+        .merge(("port", 5151))
+        .merge(("limits", body_limits(batch_limit)));
     rocket::custom(cfg)
-        .manage(SessionStore::new(session_fn))
-        .attach(CORS)
-        .register("/", catchers![not_found])
+        .manage(SessionStore::new(session_fn, result_cache))
+        .manage(BatchLimit(batch_limit))
+        .attach(CORS(config.cors_origin.clone()))
+        .attach(AdHoc::on_shutdown("Save tables", move |_| {
+            Box::pin(async move {
+                if let Err(e) = save_tables(&config, &tables) {
+                    eprintln!("Couldn't save the tables: {}", e);
+                }
+            })
+        }))
+        .register(
+            "/",
+            catchers![not_found, payload_too_large, default_catcher],
+        )
         .mount(
             "/",
             routes![
                 index,
+                post_evaluate,
+                post_analyze,
                 post_session,
+                post_session_sgf,
+                post_validate,
                 delete_session,
                 get_session_list,
                 get_session_state,
+                get_session_evaluator,
+                get_session_cache,
+                get_session_score,
+                get_session_history,
                 get_session_evaluation,
+                get_session_winrate,
+                post_session_evaluation,
+                get_session_review,
                 get_session_error,
                 put_session_move,
+                put_session_engine_move,
                 put_session_undo,
+                put_session_komi,
+                put_session_reset,
+                post_session_whatif,
             ],
         )
 }
+
+#[cfg(test)]
+mod tests {
+    use rocket::{
+        http::ContentType,
+        local::asynchronous::{Client, LocalResponse},
+        tokio::join,
+    };
+
+    use super::*;
+    use crate::store::tests::{empty, store};
+
+    #[rocket::async_test]
+    async fn concurrent_evaluations_search_once() {
+        let store = store(50);
+        let id = empty(&store, 5);
+
+        let (first, second) = join!(
+            session_evaluation(id, &store),
+            session_evaluation(id, &store)
+        );
+
+        assert_eq!(first.unwrap(), second.unwrap());
+        assert_eq!(store.get_session(&id).unwrap().searches, 1);
+        assert!(store.in_flight(id).is_none());
+    }
+
+    #[rocket::async_test]
+    async fn sessions_on_the_same_position_share_one_search() {
+        let store = store(50);
+        let first = empty(&store, 5);
+        let second = empty(&store, 5);
+
+        let a = session_evaluation(first, &store).await.unwrap();
+        let b = session_evaluation(second, &store).await.unwrap();
+
+        assert_eq!(a, b);
+        let searches = [first, second].map(|id| store.get_session(&id).unwrap().searches);
+        assert_eq!(searches.iter().sum::<usize>(), 1);
+    }
+
+    #[rocket::async_test]
+    async fn engine_moves_play_the_best_move_at_full_strength() {
+        let store = store(50);
+        let id = empty(&store, 5);
+        let (_, moves) = session_evaluation(id, &store).await.unwrap();
+        let board = store.get_session(&id).unwrap().board().clone();
+        let (best, _) = pick_best(&board, moves).unwrap();
+
+        let played = put_session_engine_move(id, None, None, (&store).into())
+            .await
+            .unwrap();
+        assert_eq!(played.mv, best);
+        assert_eq!(store.get_session(&id).unwrap().board().history.len(), 1);
+    }
+
+    #[rocket::async_test]
+    async fn seeded_engine_moves_repeat() {
+        let store = store(50);
+        let (first, second) = (empty(&store, 5), empty(&store, 5));
+
+        let mut played = Vec::new();
+        for id in [first, second] {
+            let response = put_session_engine_move(id, Some(1.0), Some(7), (&store).into())
+                .await
+                .unwrap();
+            played.push(response.mv);
+        }
+        assert_eq!(played[0], played[1]);
+    }
+
+    #[rocket::async_test]
+    async fn engine_moves_after_two_passes_are_a_conflict() {
+        let store = store(10);
+        let id = empty(&store, 3);
+        store
+            .modify_session(&id, |session| {
+                session.apply_move(Move::Pass)?;
+                session.apply_move(Move::Pass)
+            })
+            .unwrap()
+            .unwrap();
+
+        let error = put_session_engine_move(id, None, None, (&store).into())
+            .await
+            .err()
+            .unwrap();
+        assert_eq!((error.status, error.code), (Status::Conflict, "game_over"));
+        assert_eq!(store.get_session(&id).unwrap().searches, 0);
+
+        // Playing a move by hand gives the same error
+        let error = put_session_move(
+            id,
+            Json(SessionMoveRequest {
+                mv: Move::Coords((1, 1)),
+            }),
+            (&store).into(),
+        )
+        .err()
+        .unwrap();
+        assert_eq!((error.status, error.code), (Status::Conflict, "game_over"));
+    }
+
+    #[rocket::async_test]
+    async fn whatif_leaves_the_stored_session_alone() {
+        let store = store(10);
+        let id = empty(&store, 5);
+        let whatif =
+            |mv| post_session_whatif(id, None, Json(SessionMoveRequest { mv }), (&store).into());
+
+        let response = whatif(Move::Coords((2, 2))).await.unwrap();
+        assert_eq!(response.state.last_move, Some(Move::Coords((2, 2))));
+        assert!(!response.evaluation.moves.is_empty());
+
+        let session = store.get_session(&id).unwrap();
+        assert!(session.board().history.is_empty());
+        assert_eq!(session.board().get_rep(), ".".repeat(25));
+
+        let error = whatif(Move::Coords((5, 0))).await.err().unwrap();
+        assert_eq!(error.status, Status::NotAcceptable);
+    }
+
+    #[rocket::async_test]
+    async fn review_has_an_entry_per_move() {
+        let config = ServerConfig::parse(["server", "alpha-beta", "2"]).unwrap();
+        let store = SessionStore::new(session_factory(config, SharedTables::default()), 16);
+        let id = empty(&store, 3);
+        let played = [Move::Coords((1, 1)), Move::Coords((0, 0)), Move::Pass];
+        store
+            .modify_session(&id, |session| {
+                played.iter().try_for_each(|&mv| session.apply_move(mv))
+            })
+            .unwrap()
+            .unwrap();
+
+        for param in [0, 256, 1000] {
+            let error = get_session_review(id, Some(param), (&store).into())
+                .await
+                .err()
+                .unwrap();
+            assert_eq!(error.status, Status::BadRequest, "{param}");
+        }
+
+        let review = get_session_review(id, Some(2), (&store).into())
+            .await
+            .unwrap();
+        assert_eq!(review.len(), 3);
+        for (i, entry) in review.iter().enumerate() {
+            assert_eq!(entry.move_number, i + 1);
+            assert_eq!(entry.played, played[i]);
+            assert!(matches!(entry.best, Move::Coords(_) | Move::Pass));
+        }
+    }
+
+    #[rocket::async_test]
+    async fn winrate_names_the_side_that_has_won() {
+        let alpha_beta = ServerConfig::parse(["server", "alpha-beta", "1"]).unwrap();
+        let stores = [
+            SessionStore::new(session_factory(alpha_beta, SharedTables::default()), 16),
+            store(50),
+        ];
+        let won = |owner: char, turn| {
+            let rep = "XXXXXX.X.XXXXXXX.X.XXXXXX".replace('X', &owner.to_string());
+            Board::from_rep(rep, 5, turn, 0.5).unwrap()
+        };
+
+        for store in &stores {
+            for (board, winner) in [
+                (won('X', Turn::White), Turn::Black),
+                (won('O', Turn::Black), Turn::White),
+            ] {
+                // The shared result cache answers the second session without searching it
+                let first = store.create_session_from_board(board.clone()).session_id;
+                session_evaluation(first, store).await.unwrap();
+                let id = store.create_session_from_board(board).session_id;
+
+                let winrate = get_session_winrate(id, store.into()).await.unwrap();
+                assert_eq!(winrate.winner_hint, winner);
+                match winner {
+                    Turn::Black => assert!(winrate.score > 0.0, "{}", winrate.score),
+                    _ => assert!(winrate.score < 0.0, "{}", winrate.score),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_evaluators_are_bad_requests() {
+        for (algorithm, param) in [
+            ("alpha-beta", Some(0)),
+            ("alpha-beta", Some(300)),
+            ("monte-carlo", Some(MAX_REQUEST_SECONDS + 1)),
+            ("minimax", None),
+        ] {
+            let error = create_evaluator(algorithm, param, 5).err().unwrap();
+            assert!(matches!(error, EvalError::InvalidParam(_)), "{algorithm}");
+            assert_eq!(ApiError::from(error).status, Status::BadRequest);
+        }
+        assert!(create_evaluator(" Alpha-Beta ", Some(2), 5).is_ok());
+    }
+
+    #[test]
+    fn request_tables_are_sized_for_the_board() {
+        let capacity = |size| match create_evaluator("alpha-beta", Some(2), size).unwrap() {
+            AnyEvaluator::AlphaBeta(evaluator) => evaluator.table_stats().unwrap().capacity,
+            _ => unreachable!(),
+        };
+        assert_eq!(capacity(19), BATCH_CACHE_CAPACITY);
+        assert_eq!(capacity(5), BATCH_CACHE_CAPACITY * 25 / 361);
+    }
+
+    #[rocket::async_test]
+    async fn restricted_sessions_stay_off_the_edge_early() {
+        let config = ServerConfig::parse(["server", "alpha-beta", "1"]).unwrap();
+        let store = SessionStore::new(session_factory(config, SharedTables::default()), 16);
+        let data: SessionCreateData = rocket::serde::json::from_str(&format!(
+            r#"{{"turn":"Black","size":5,"rep":"{}","komi":0.5,"move_restriction":{{"moves":2,"lines":1}}}}"#,
+            ".".repeat(25)
+        ))
+        .unwrap();
+        let id = store.create_new_session(&data.into()).unwrap().session_id;
+        let on_edge = |moves: &[(Move, f32)]| {
+            moves.iter().any(|(mv, _)| {
+                matches!(mv, Move::Coords((r, c)) if [*r, *c].iter().any(|&x| x == 0 || x == 4))
+            })
+        };
+
+        let (_, moves) = session_evaluation(id, &store).await.unwrap();
+        assert!(moves.iter().any(|(mv, _)| matches!(mv, Move::Coords(_))));
+        assert!(!on_edge(&moves));
+
+        store
+            .modify_session(&id, |session| {
+                [Move::Coords((2, 2)), Move::Coords((1, 1))]
+                    .iter()
+                    .try_for_each(|&mv| session.apply_move(mv))
+            })
+            .unwrap()
+            .unwrap();
+        let (_, moves) = session_evaluation(id, &store).await.unwrap();
+        assert!(on_edge(&moves));
+    }
+
+    #[rocket::async_test]
+    async fn cache_reports_the_states_a_search_stored() {
+        let config = ServerConfig::parse(["server", "alpha-beta", "2"]).unwrap();
+        let store = SessionStore::new(session_factory(config, SharedTables::default()), 16);
+        let id = empty(&store, 3);
+        let cache = |store| get_session_cache(id, store).unwrap().into_inner();
+        assert_eq!(cache((&store).into()).stored_states, Some(0));
+
+        session_evaluation(id, &store).await.unwrap();
+        let data = cache((&store).into());
+        assert!(data.stored_states.unwrap() > 0);
+        assert!(data.fill_ratio.unwrap() > 0.0);
+        assert!(data.tree_nodes.is_none());
+    }
+
+    async fn client(batch_limit: usize) -> Client {
+        let cfg = Figment::from(rocket::Config::debug_default())
+            .merge(("log_level", "off"))
+            .merge(("limits", body_limits(batch_limit)));
+        let rocket = rocket::custom(cfg)
+            .manage(store(10))
+            .manage(BatchLimit(batch_limit))
+            .register("/", catchers![payload_too_large])
+            .mount(
+                "/",
+                routes![
+                    post_session,
+                    post_session_sgf,
+                    post_validate,
+                    get_session_history,
+                    get_session_evaluation,
+                    post_evaluate,
+                    post_analyze,
+                    put_session_move
+                ],
+            );
+        Client::tracked(rocket).await.unwrap()
+    }
+
+    fn board_body(rep: &str) -> String {
+        format!(r#"{{"turn":"Black","size":5,"rep":"{rep}","komi":0.5}}"#)
+    }
+
+    async fn error_code(response: LocalResponse<'_>) -> String {
+        let body = response.into_string().await.unwrap();
+        let body = rocket::serde::json::from_str::<rocket::serde::json::Value>(&body).unwrap();
+        body["code"].as_str().unwrap().to_string()
+    }
+
+    #[rocket::async_test]
+    async fn bodies_over_the_route_limit_are_rejected() {
+        let client = client(4).await;
+        let rep = ".".repeat(BOARD_BODY_LIMIT * 2);
+
+        let response = client
+            .post("/session")
+            .header(ContentType::JSON)
+            .body(board_body(&rep))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::PayloadTooLarge);
+        assert_eq!(error_code(response).await, "payload_too_large");
+
+        // Too large for a single board route, but within the batch limit
+        let batch = format!(
+            r#"{{"boards":[{}],"algorithm":"alpha-beta","param":1}}"#,
+            board_body(&rep)
+        );
+        let response = client
+            .post("/evaluate")
+            .header(ContentType::JSON)
+            .body(batch)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::BadRequest);
+
+        let boards = vec![board_body(&rep); 3].join(",");
+        let response = client
+            .post("/evaluate")
+            .header(ContentType::JSON)
+            .body(format!(
+                r#"{{"boards":[{boards}],"algorithm":"alpha-beta"}}"#
+            ))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::PayloadTooLarge);
+        assert_eq!(error_code(response).await, "payload_too_large");
+    }
+
+    async fn post_json<'c>(client: &'c Client, uri: &'c str, body: String) -> LocalResponse<'c> {
+        client
+            .post(uri)
+            .header(ContentType::JSON)
+            .body(body)
+            .dispatch()
+            .await
+    }
+
+    async fn json_body<T: DeserializeOwned>(response: LocalResponse<'_>) -> T {
+        let body = response.into_string().await.unwrap();
+        rocket::serde::json::from_str(&body).unwrap()
+    }
+
+    #[rocket::async_test]
+    async fn batches_come_back_in_input_order() {
+        let client = client(4).await;
+        let five = board_body(&".".repeat(25));
+        let three = r#"{"turn":"White","size":3,"rep":".........","komi":0.5}"#;
+        let body = format!(r#"{{"boards":[{five},{three}],"algorithm":"alpha-beta","param":1}}"#);
+
+        let response = post_json(&client, "/evaluate", body).await;
+        assert_eq!(response.status(), Status::Ok);
+        let results = json_body::<Vec<SessionEvaluationData>>(response).await;
+        let counts = results.iter().map(|r| r.moves.len()).collect::<Vec<_>>();
+        assert_eq!(counts, vec![26, 10]);
+    }
+
+    #[rocket::async_test]
+    async fn failures_come_back_as_json_errors() {
+        let client = client(4).await;
+        let response = post_json(
+            &client,
+            "/session",
+            board_body(&format!("X{}", ".".repeat(24))),
+        )
+        .await;
+        let id = json_body::<SessionIdentifier>(response).await.session_id;
+        let put_move = |uri: String| {
+            client
+                .put(uri)
+                .header(ContentType::JSON)
+                .body(r#"{"mv":{"Coords":[0,0]}}"#)
+                .dispatch()
+        };
+
+        let response = put_move(format!("/session/{id}/move")).await;
+        assert_eq!(response.status(), Status::NotAcceptable);
+        let body = json_body::<rocket::serde::json::Value>(response).await;
+        assert_eq!(body["code"], "illegal_move");
+        assert_eq!(body["message"], "Tile is occupied (Place(0))");
+
+        let response = put_move(format!("/session/{}/move", id + 1)).await;
+        assert_eq!(response.status(), Status::NotFound);
+        let body = json_body::<rocket::serde::json::Value>(response).await;
+        assert_eq!(body["code"], "session_not_found");
+        assert!(body["message"].as_str().is_some_and(|m| !m.is_empty()));
+    }
+
+    #[rocket::async_test]
+    async fn sgf_sessions_replay_every_move() {
+        let client = client(4).await;
+        let sgf = "(;GM[1]FF[4]SZ[5]KM[5.5];B[cc];W[dc];B[cd];W[];B[dd])";
+        let response = client.post("/session/sgf").body(sgf).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let id = json_body::<SessionIdentifier>(response).await.session_id;
+
+        let history = || async {
+            let response = client
+                .get(format!("/session/{id}/history"))
+                .dispatch()
+                .await;
+            let history = json_body::<rocket::serde::json::Value>(response).await;
+            history["moves"].as_array().unwrap().clone()
+        };
+        let moves = history().await;
+        assert_eq!(moves.len(), 5);
+        assert_eq!(moves[3]["mv"], "Pass");
+
+        // The first move after the import keeps its evaluation at its own index
+        let response = client
+            .get(format!("/session/{id}/evaluation"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let response = client
+            .put(format!("/session/{id}/move"))
+            .header(ContentType::JSON)
+            .body(r#"{"mv":{"Coords":[0,0]}}"#)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let moves = history().await;
+        assert_eq!(moves.len(), 6);
+        for imported in &moves[..5] {
+            assert!(imported["metadata"]["evaluation"].is_null());
+        }
+        assert!(moves[5]["metadata"]["evaluation"].is_number());
+
+        // A move onto an occupied point fails the import
+        let response = client
+            .post("/session/sgf")
+            .body("(;SZ[5];B[cc];W[cc])")
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::BadRequest);
+        assert_eq!(error_code(response).await, "bad_request");
+    }
+
+    #[rocket::async_test]
+    async fn validation_stops_at_an_immediate_ko_retake() {
+        let client = client(4).await;
+        // Black takes the ko at c4, White retakes straight away at b4
+        let rep = [".XO..", "XO.O.", ".XO..", ".....", "....."].concat();
+        let replay = format!("5:black:0.5:{rep}|B c4,W b4,B e1");
+        let response = client.post("/validate").body(replay).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+
+        let body = json_body::<rocket::serde::json::Value>(response).await;
+        assert_eq!(body["valid"], false);
+        let moves = body["moves"].as_array().unwrap();
+        assert_eq!(moves.len(), 2);
+        assert_eq!(moves[0]["legal"], true);
+        assert_eq!(moves[1]["index"], 1);
+        assert_eq!(moves[1]["legal"], false);
+        assert_eq!(moves[1]["reason"], "repetition");
+        assert_eq!(moves[1]["mv"]["Coords"], rocket::serde::json::json!([1, 1]));
+    }
+
+    #[rocket::async_test]
+    async fn analyze_ranks_the_moves_with_both_algorithms() {
+        let client = client(4).await;
+        let board = r#""turn":"Black","size":3,"rep":".........","komi":0.5"#;
+        for budget in [
+            r#""depth":0"#,
+            r#""depth":300"#,
+            r#""time":0"#,
+            r#""time":1000"#,
+        ] {
+            let body = format!("{{{board},{budget}}}");
+            let response = post_json(&client, "/analyze", body).await;
+            assert_eq!(response.status(), Status::BadRequest, "{budget}");
+            assert_eq!(error_code(response).await, "bad_request");
+        }
+
+        let body = format!(r#"{{{board},"depth":2,"time":1}}"#);
+        let response = post_json(&client, "/analyze", body).await;
+        assert_eq!(response.status(), Status::Ok);
+        let analysis = json_body::<AnalyzeResponse>(response).await;
+        for ranked in [analysis.alpha_beta, analysis.monte_carlo] {
+            assert!(!ranked.moves.is_empty());
+            assert!(ranked.moves.windows(2).all(|w| w[0].1 >= w[1].1));
+        }
+    }
+
+    #[rocket::async_test]
+    async fn request_budgets_are_validated() {
+        let client = client(4).await;
+        for (algorithm, param) in [
+            ("alpha-beta", 0),
+            ("alpha-beta", 256),
+            ("alpha-beta", 1000),
+            ("monte-carlo", 0),
+            ("monte-carlo", 1000),
+        ] {
+            let body = format!(
+                r#"{{"boards":[{}],"algorithm":"{algorithm}","param":{param}}}"#,
+                board_body(&".".repeat(25))
+            );
+            let response = post_json(&client, "/evaluate", body).await;
+            assert_eq!(response.status(), Status::BadRequest, "{algorithm} {param}");
+            assert_eq!(error_code(response).await, "bad_request");
+        }
+    }
+
+    #[test]
+    fn sessions_on_one_size_share_a_table() {
+        let config = ServerConfig::parse(["server", "alpha-beta", "2"]).unwrap();
+        let tables = SharedTables::default();
+        let store = SessionStore::new(session_factory(config, tables.clone()), 16);
+        let table = |size| {
+            let id = empty(&store, size);
+            match &store.get_session(&id).unwrap().evaluation_session {
+                AnyEvaluationSession::AlphaBeta(a) => a.table().unwrap(),
+                AnyEvaluationSession::MonteCarlo(_) => panic!("expected an alpha-beta session"),
+            }
+        };
+
+        let (first, second, other) = (table(5), table(5), table(3));
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(!Arc::ptr_eq(&first, &other));
+        assert_eq!(tables.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn small_boards_get_smaller_tables() {
+        let config = ServerConfig::parse(["server", "alpha-beta", "2"]).unwrap();
+        let store = SessionStore::new(session_factory(config.clone(), SharedTables::default()), 16);
+        let capacity = |size| {
+            let id = empty(&store, size);
+            match &store.get_session(&id).unwrap().evaluation_session {
+                AnyEvaluationSession::AlphaBeta(a) => a.table_stats().unwrap().capacity,
+                AnyEvaluationSession::MonteCarlo(_) => panic!("expected an alpha-beta session"),
+            }
+        };
+
+        let (small, large) = (capacity(5), capacity(13));
+        assert_eq!(small, config.table_capacity_for(5));
+        assert_eq!(large, config.table_capacity_for(13));
+        // 25 points against 169
+        assert!(small * 6 < large, "{small} {large}");
+        assert!(large < config.table_capacity);
+    }
+
+    #[test]
+    fn tables_are_saved_to_and_opened_from_the_persistence_dir() {
+        let dir = std::env::temp_dir().join(format!("ipvgo-tables-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let flag = format!("--persistence-dir={}", dir.display());
+        let config = ServerConfig::parse(["server", "alpha-beta", flag.as_str()]).unwrap();
+        assert_eq!(open_table(&config, 5).len(), 0);
+
+        let mut table = open_table(&config, 5);
+        let entry = evaluation::alphabeta::TranspositionEntry {
+            depth: 2,
+            value: 1.0,
+            bound: evaluation::alphabeta::Bound::Exact,
+            reached: 2,
+        };
+        table.insert(7, entry);
+        let tables = SharedTables::default();
+        tables
+            .lock()
+            .unwrap()
+            .insert(5, Arc::new(RwLock::new(table)));
+        save_tables(&config, &tables).unwrap();
+
+        assert_eq!(open_table(&config, 5).len(), 1);
+        assert_eq!(open_table(&config, 9).len(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
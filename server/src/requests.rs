@@ -1,9 +1,10 @@
 use std::time::Duration;
 
-use board::{Board, Move, Turn};
+use board::{Board, GameResult, HeuristicMode, LibertySummary, Move, Turn};
+use evaluation::{EvalDescription, MoveRestriction, Strength};
 use rocket::serde::{Deserialize, Serialize};
 
-use crate::store::BoardData;
+use crate::store::{BoardData, MoveMetadata};
 
 #[derive(Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
@@ -18,15 +19,21 @@ pub struct SessionCreateData {
     pub size: u8,
     pub rep: String,
     pub komi: f32,
+    pub heuristic: Option<HeuristicMode>,
+    pub strength: Option<Strength>,
+    pub move_restriction: Option<MoveRestriction>,
 }
 
-impl Into<BoardData> for SessionCreateData {
-    fn into(self) -> BoardData {
+impl From<SessionCreateData> for BoardData {
+    fn from(data: SessionCreateData) -> Self {
         BoardData {
-            turn: self.turn,
-            komi: self.komi,
-            rep: self.rep,
-            size: self.size,
+            turn: data.turn,
+            komi: data.komi,
+            rep: data.rep,
+            size: data.size,
+            heuristic: data.heuristic.unwrap_or_default(),
+            strength: data.strength,
+            move_restriction: data.move_restriction,
         }
     }
 }
@@ -39,19 +46,104 @@ pub struct SessionBoardState {
     pub rep: String,
 
     pub komi: f32,
+
+    pub game_over: bool,
+    pub must_pass: bool,
+    pub last_move: Option<Move>,
+    pub end_reason: Option<String>,
+    pub result: Option<GameResult>,
+    // only filled when asked for, indexed like rep
+    pub legal: Option<Vec<bool>>,
 }
 
 impl SessionBoardState {
     pub fn new(board: &Board) -> Self {
+        let game_over = board.turn == Turn::None;
+        let passes = board.history.iter().rev().take(2);
+        let end_reason = if game_over && passes.filter(|h| h.action == Move::Pass).count() == 2 {
+            Some("two_passes".to_string())
+        } else {
+            None
+        };
+
         Self {
             size: board.size,
             turn: board.turn,
             komi: board.komi,
             rep: board.get_rep(),
+            game_over,
+            must_pass: board.must_pass(),
+            last_move: board.last_move(),
+            end_reason,
+            result: board.result(),
+            legal: None,
+        }
+    }
+
+    pub fn with_legal(mut self, board: &Board) -> Self {
+        self.legal = Some(board.legal_map());
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SessionEvaluatorData {
+    pub algorithm: String,
+    pub depth: Option<u8>,
+    pub time: Option<Duration>,
+    pub cache: bool,
+    pub strength: Option<Strength>,
+    pub restriction: Option<MoveRestriction>,
+    pub tie_seed: Option<u64>,
+    pub endgame: Option<(f32, u8)>,
+    pub temperature: Option<f32>,
+    pub seed: Option<u64>,
+}
+
+impl From<EvalDescription> for SessionEvaluatorData {
+    fn from(description: EvalDescription) -> Self {
+        SessionEvaluatorData {
+            algorithm: description.algorithm,
+            depth: description.depth,
+            time: description.time,
+            cache: description.cache,
+            strength: description.strength,
+            restriction: description.restriction,
+            tie_seed: description.tie_seed,
+            endgame: description.endgame,
+            temperature: description.temperature,
+            seed: description.seed,
         }
     }
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SessionCacheData {
+    pub stored_states: Option<usize>,
+    pub capacity: Option<usize>,
+    pub fill_ratio: Option<f32>,
+    pub hit_rate: Option<f32>,
+    pub tree_nodes: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SessionScoreData {
+    pub current_score: f32,
+    pub liberties: LibertySummary,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SessionWinrateData {
+    pub score: f32,
+    pub winner_hint: Turn,
+    // how clear the lead is, from 0 for even to 1 for winning by the whole board
+    pub confidence: f32,
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
 pub struct SessionMoveRequest {
@@ -71,12 +163,31 @@ impl SessionMoveResponse {
     }
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SessionKomiRequest {
+    pub komi: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SessionKomiResponse {
+    pub state: SessionBoardState,
+    pub current_score: f32,
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
 pub struct SessionUndoResponse {
     pub state: SessionBoardState,
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SessionResetResponse {
+    pub state: SessionBoardState,
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
 pub struct SessionListData {
@@ -89,3 +200,147 @@ pub struct SessionEvaluationData {
     pub time: Duration,
     pub moves: Vec<(Move, f32)>,
 }
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SessionCandidatesRequest {
+    pub candidates: Vec<Move>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct BatchEvaluationRequest {
+    pub boards: Vec<SessionCreateData>,
+    pub algorithm: String,
+    pub param: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AnalyzeRequest {
+    #[serde(flatten)]
+    pub board: SessionCreateData,
+    pub time: Option<usize>,
+    pub depth: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AnalyzeResponse {
+    pub alpha_beta: SessionEvaluationData,
+    pub monte_carlo: SessionEvaluationData,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SessionWhatIfResponse {
+    pub mv: Move,
+    pub state: SessionBoardState,
+    pub evaluation: SessionEvaluationData,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SessionReviewEntry {
+    pub move_number: usize,
+    pub played: Move,
+    pub best: Move,
+    pub score: f32,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SessionHistoryEntry {
+    pub mv: Move,
+    pub metadata: Option<MoveMetadata>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SessionHistoryData {
+    pub moves: Vec<SessionHistoryEntry>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct MoveValidation {
+    pub index: usize,
+    pub mv: Move,
+    pub legal: bool,
+    pub reason: Option<&'static str>,
+    pub message: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ValidationData {
+    pub valid: bool,
+    pub moves: Vec<MoveValidation>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitized_evaluations_are_valid_json() {
+        let evaluation = |moves: Vec<(Move, f32)>| SessionEvaluationData {
+            time: Duration::from_millis(5),
+            moves,
+        };
+        let moves = vec![
+            (Move::Pass, f32::NAN),
+            (Move::Coords((0, 0)), f32::INFINITY),
+            (Move::Coords((1, 1)), f32::NEG_INFINITY),
+        ];
+        let sanitized = moves
+            .iter()
+            .map(|&(mv, s)| (mv, evaluation::finite_score(s)))
+            .collect::<Vec<_>>();
+
+        let json = rocket::serde::json::to_string(&evaluation(sanitized.clone())).unwrap();
+        let parsed = rocket::serde::json::from_str::<SessionEvaluationData>(&json).unwrap();
+        assert_eq!(parsed.moves, sanitized);
+
+        // Raw non-finite scores turn into nulls that don't read back as numbers
+        let json = rocket::serde::json::to_string(&evaluation(moves)).unwrap();
+        assert!(rocket::serde::json::from_str::<SessionEvaluationData>(&json).is_err());
+    }
+
+    #[test]
+    fn two_passes_end_the_game_with_a_result() {
+        let mut board = Board::from_rep(".........".to_string(), 3, Turn::Black, 0.5).unwrap();
+        board.apply_move(Move::Coords((1, 1))).unwrap();
+        board.apply_move(Move::Pass).unwrap();
+        assert!(!SessionBoardState::new(&board).game_over);
+
+        board.apply_move(Move::Pass).unwrap();
+        let state = SessionBoardState::new(&board);
+        assert!(state.game_over);
+        assert_eq!(state.turn, Turn::None);
+        assert_eq!(state.end_reason.as_deref(), Some("two_passes"));
+        let result = state.result.unwrap();
+        assert_eq!((result.winner, result.margin), (Turn::Black, 8.5));
+    }
+
+    #[test]
+    fn legal_maps_leave_out_the_ko_point() {
+        // Black takes at (1, 2), White can't retake at (1, 1) right away. (0, 0) is suicide for
+        // White, every other empty point is open
+        let rep = [".XO..", "XO.O.", ".XO..", ".....", "....#"].concat();
+        let mut board = Board::from_rep(rep, 5, Turn::Black, 0.5).unwrap();
+        board.apply_move(Move::Coords((1, 2))).unwrap();
+
+        let state = SessionBoardState::new(&board);
+        assert!(state.legal.is_none());
+        let legal = state.with_legal(&board).legal.unwrap();
+        assert_eq!(legal.len(), 25);
+        assert_eq!(board.get_rep().chars().nth(6), Some('.'));
+        assert!(!legal[6] && !legal[0]);
+        for (p, tile) in board.get_rep().chars().enumerate() {
+            if p != 0 && p != 6 {
+                assert_eq!(legal[p], tile == '.', "{p}");
+            }
+        }
+    }
+}
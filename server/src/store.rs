@@ -1,27 +1,107 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     ops::AddAssign,
-    sync::{LazyLock, Mutex},
-    time::Duration,
+    sync::{Arc, LazyLock, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
-use board::{Board, Move, Turn};
-use evaluation::{AnyEvaluationSession, EvaluationSession};
+use board::{error::BoardError, Board, HeuristicMode, Move, Turn};
+use evaluation::{
+    AnyEvaluationSession, EvalDescription, EvaluationSession, MoveRestriction, Strength,
+};
+use rocket::{serde::Serialize, tokio::sync::OnceCell};
 
 use crate::requests::SessionIdentifier;
 
 static CURRENT_ID: LazyLock<Mutex<usize>> = LazyLock::new(|| Mutex::new(0));
 
+pub type EvaluationResult = Result<(Duration, Vec<(Move, f32)>), String>;
+pub type PendingEvaluation = Arc<OnceCell<EvaluationResult>>;
+// The history digest keeps positions apart that only differ in which retakes superko forbids
+pub type ResultKey = (u64, u64, EvalDescription);
+pub type SessionHandle = Arc<Mutex<Session>>;
+
+// Final move rankings by position and evaluator, dropping the least recently used
+pub struct ResultCache {
+    capacity: usize,
+    entries: HashMap<ResultKey, (Duration, Vec<(Move, f32)>)>,
+    order: VecDeque<ResultKey>,
+}
+
+impl ResultCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    pub fn get(&mut self, key: &ResultKey) -> Option<(Duration, Vec<(Move, f32)>)> {
+        let result = self.entries.get(key)?.clone();
+        self.touch(key);
+        Some(result)
+    }
+
+    pub fn insert(&mut self, key: ResultKey, result: (Duration, Vec<(Move, f32)>)) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.entries.insert(key.clone(), result).is_some() {
+            self.touch(&key);
+            return;
+        }
+        self.order.push_back(key);
+        if self.entries.len() > self.capacity {
+            let removal = self.order.pop_front().unwrap();
+            self.entries.remove(&removal);
+        }
+    }
+
+    fn touch(&mut self, key: &ResultKey) {
+        if let Some(i) = self.order.iter().position(|k| k == key) {
+            let key = self.order.remove(i).unwrap();
+            self.order.push_back(key);
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct MoveMetadata {
+    pub timestamp: Duration,
+    pub evaluation: Option<f32>,
+}
+
+impl MoveMetadata {
+    pub fn new(evaluation: Option<f32>) -> Self {
+        Self {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default(),
+            evaluation,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Session {
     pub session_id: usize,
     pub evaluation_cache: Option<(Duration, Vec<(Move, f32)>)>,
     pub evaluation_session: AnyEvaluationSession<Board>,
+    pub move_metadata: Vec<MoveMetadata>,
+    // Moves the session was created with, like an imported game. Reset goes back to there
+    pub imported: usize,
+    // Bumped by every change, a search detached before a change isn't put back
+    pub version: usize,
+    // Searches run on the stored session, shared and cached results don't count
+    pub searches: usize,
 }
 
 impl Session {
     pub fn board(&self) -> &Board {
-        &self.evaluation_session.get_root()
+        self.evaluation_session.get_root()
     }
 }
 
@@ -30,87 +110,520 @@ pub struct BoardData {
     pub size: u8,
     pub turn: Turn,
     pub komi: f32,
+    pub heuristic: HeuristicMode,
+    pub strength: Option<Strength>,
+    // root placements kept off the outer lines early in the game
+    pub move_restriction: Option<MoveRestriction>,
+}
+
+impl BoardData {
+    pub fn to_board(&self) -> Result<Board, BoardError> {
+        let mut board = Board::from_rep(self.rep.clone(), self.size, self.turn, self.komi)?;
+        board.heuristic_mode = self.heuristic;
+        Ok(board)
+    }
 }
 
 impl Session {
     pub fn new(
         data: &BoardData,
         session_fn: impl Fn(Board) -> AnyEvaluationSession<Board>,
-    ) -> Result<Self, String> {
+    ) -> Result<Self, BoardError> {
+        let mut session = Self::from_board(data.to_board()?, session_fn);
+        if let Some(strength) = data.strength {
+            session.evaluation_session = session.evaluation_session.with_strength(strength);
+        }
+        if data.move_restriction.is_some() {
+            session.evaluation_session = session
+                .evaluation_session
+                .with_move_restriction(data.move_restriction);
+        }
+        Ok(session)
+    }
+
+    pub fn from_board(
+        board: Board,
+        session_fn: impl Fn(Board) -> AnyEvaluationSession<Board>,
+    ) -> Self {
         let mut handle = CURRENT_ID.lock().unwrap();
         handle.add_assign(1);
-        let id = handle.clone();
+        let id = *handle;
 
-        let board = Board::from_rep(data.rep.clone(), data.size, data.turn, data.komi)?;
+        // Imported moves get metadata without an evaluation, so it stays indexed like the history
+        let imported = board.history.len();
+        let move_metadata = vec![MoveMetadata::new(None); imported];
 
-        Ok(Self {
+        Self {
             session_id: id,
             evaluation_cache: None,
             evaluation_session: session_fn(board),
-        })
+            move_metadata,
+            imported,
+            version: 0,
+            searches: 0,
+        }
     }
 }
 
 impl Session {
-    pub fn apply_move(&mut self, mv: Move) -> Result<(), String> {
+    pub fn apply_move(&mut self, mv: Move) -> Result<(), BoardError> {
+        // Search trees key their children by Place moves, out of bounds coords are left for
+        // the board to reject
+        let size = self.board().size as usize;
+        let mv = match mv {
+            Move::Coords((x, y)) if x < size && y < size => self.board().to_place_move(mv),
+            mv => mv,
+        };
+        let coords = self.board().to_coords_move(mv);
+        let evaluation = self
+            .evaluation_cache
+            .as_ref()
+            .and_then(|c| c.1.iter().find(|m| m.0 == coords).map(|m| m.1));
+
         self.evaluation_session.apply_move(mv)?;
+        self.evaluation_session.get_root_mut().compact_chains();
         self.evaluation_cache = None;
+        self.move_metadata.push(MoveMetadata::new(evaluation));
         Ok(())
     }
 
-    pub fn undo_move(&mut self) -> Result<(), String> {
+    pub fn undo_move(&mut self) -> Result<(), BoardError> {
         self.evaluation_session.undo_move()?;
         self.evaluation_cache = None;
+        self.move_metadata.pop();
+        Ok(())
+    }
+
+    pub fn set_komi(&mut self, komi: f32) -> Result<(), BoardError> {
+        self.evaluation_session.get_root_mut().set_komi(komi)?;
+        self.evaluation_session.clear_search();
+        self.evaluation_cache = None;
+        Ok(())
+    }
+
+    pub fn is_at(&self, board: &Board) -> bool {
+        let current = self.board();
+        current.history.len() == board.history.len()
+            && current.compute_board_hash() == board.compute_board_hash()
+    }
+
+    // Searches the stored session, so a tree grown here is reused by the next evaluation. The
+    // session is only locked to take the search out and to put it back, reads and moves go on
+    // in the meantime. None when the session has moved on from the requested position
+    pub fn search_in_place(handle: &SessionHandle, board: &Board) -> Option<EvaluationResult> {
+        let (version, mut search) = {
+            let mut session = handle.lock().unwrap();
+            if !session.is_at(board) {
+                return None;
+            }
+            session.searches += 1;
+            (session.version, session.evaluation_session.detach_search())
+        };
+
+        let start = Instant::now();
+        let result = search
+            .evaluate()
+            .map(|moves| {
+                let moves = moves
+                    .into_iter()
+                    .map(|(m, v)| (board.to_coords_move(m), v))
+                    .collect::<Vec<_>>();
+                (start.elapsed(), moves)
+            })
+            .map_err(|e| e.to_string());
+
+        let mut session = handle.lock().unwrap();
+        if session.version == version {
+            session.evaluation_session.attach_search(search);
+            if let Ok(result) = &result {
+                session.evaluation_cache = Some(result.clone());
+            }
+        }
+        Some(result)
+    }
+
+    pub fn reset(&mut self) -> Result<(), BoardError> {
+        while self.board().history.len() > self.imported {
+            self.undo_move()?;
+        }
         Ok(())
     }
 }
 
+// The store lock only guards the map, each session has its own lock so a long
+// evaluation of one session doesn't hold up the others
 pub struct SessionStore {
-    pub sessions: Mutex<HashMap<usize, Session>>,
+    pub sessions: Mutex<HashMap<usize, SessionHandle>>,
     pub session_fn: Box<dyn Send + Sync + 'static + Fn(Board) -> AnyEvaluationSession<Board>>,
+    // Evaluations still running, keyed by session with the history length and board hash
+    pub pending: Mutex<HashMap<usize, (usize, u64, PendingEvaluation)>>,
+    pub results: Mutex<ResultCache>,
 }
 
 impl SessionStore {
     pub fn new(
         session_fn: impl Send + Sync + 'static + Fn(Board) -> AnyEvaluationSession<Board>,
+        result_capacity: usize,
     ) -> Self {
         Self {
             sessions: Mutex::new(HashMap::new()),
             session_fn: Box::new(session_fn),
+            pending: Mutex::new(HashMap::new()),
+            results: Mutex::new(ResultCache::new(result_capacity)),
         }
     }
 
-    pub fn get_session(&self, id: &usize) -> Result<Session, String> {
+    pub fn session_handle(&self, id: &usize) -> Result<SessionHandle, String> {
         let handle = self.sessions.lock().unwrap();
-        let value = handle.get(id);
-        match value {
-            Some(v) => Ok(v.clone()),
-            None => Err(String::from("The specified session does not exist")),
+        handle
+            .get(id)
+            .cloned()
+            .ok_or(String::from("The specified session does not exist"))
+    }
+
+    // Searches run outside the session lock, so this only waits for the copy
+    pub fn get_session(&self, id: &usize) -> Result<Session, String> {
+        let handle = self.session_handle(id)?;
+        let session = handle.lock().unwrap().clone();
+        Ok(session)
+    }
+
+    // Holds the session lock for the whole change, so concurrent changes apply one after another.
+    // Changes are made in place, the board leaves itself untouched when a change fails
+    pub fn modify_session<R, E>(
+        &self,
+        id: &usize,
+        change: impl FnOnce(&mut Session) -> Result<R, E>,
+    ) -> Result<Result<R, E>, String> {
+        let handle = self.session_handle(id)?;
+        let mut session = handle.lock().unwrap();
+        let result = change(&mut session);
+        if result.is_ok() {
+            session.version += 1;
         }
+        Ok(result)
     }
 
-    pub fn update_session(&self, id: usize, session: Session) {
-        let mut handle = self.sessions.lock().unwrap();
-        handle.insert(id, session);
+    pub fn update_evaluation_cache(
+        &self,
+        id: usize,
+        evaluated: &Board,
+        cache: (Duration, Vec<(Move, f32)>),
+    ) {
+        if let Ok(handle) = self.session_handle(&id) {
+            let mut session = handle.lock().unwrap();
+            if session.is_at(evaluated) {
+                session.evaluation_cache = Some(cache);
+            }
+        }
+    }
+
+    // Requests for a position that is already being evaluated share that evaluation
+    pub fn pending_evaluation(&self, id: usize, board: &Board) -> PendingEvaluation {
+        let key = (board.history.len(), board.compute_board_hash());
+        let mut handle = self.pending.lock().unwrap();
+        match handle.get(&id) {
+            Some((len, hash, pending)) if (*len, *hash) == key => pending.clone(),
+            _ => {
+                let pending = PendingEvaluation::default();
+                handle.insert(id, (key.0, key.1, pending.clone()));
+                pending
+            }
+        }
+    }
+
+    // The running evaluation of a session with the history length and board hash it was started
+    // at, found without locking the session
+    pub fn in_flight(&self, id: usize) -> Option<(usize, u64, PendingEvaluation)> {
+        self.pending.lock().unwrap().get(&id).cloned()
+    }
+
+    pub fn finish_evaluation(&self, id: usize, pending: &PendingEvaluation) {
+        let mut handle = self.pending.lock().unwrap();
+        if handle
+            .get(&id)
+            .is_some_and(|(_, _, p)| Arc::ptr_eq(p, pending))
+        {
+            handle.remove(&id);
+        }
     }
 
-    pub fn create_new_session(&self, data: &BoardData) -> Result<SessionIdentifier, String> {
+    pub fn create_new_session(&self, data: &BoardData) -> Result<SessionIdentifier, BoardError> {
         let session = Session::new(data, self.session_fn.as_ref())?;
+        Ok(self.insert_session(session))
+    }
+
+    pub fn create_session_from_board(&self, board: Board) -> SessionIdentifier {
+        self.insert_session(Session::from_board(board, self.session_fn.as_ref()))
+    }
+
+    fn insert_session(&self, session: Session) -> SessionIdentifier {
         let id = session.session_id;
 
         let mut handle = self.sessions.lock().unwrap();
-        handle.insert(session.session_id, session);
+        handle.insert(session.session_id, Arc::new(Mutex::new(session)));
 
-        Ok(SessionIdentifier { session_id: id })
+        SessionIdentifier { session_id: id }
     }
 
     pub fn delete_session(&self, id: &usize) -> Result<(), String> {
         let mut handle = self.sessions.lock().unwrap();
-        if let Some(_) = handle.get(&id) {
-            handle.remove(&id);
+        if handle.get(id).is_some() {
+            handle.remove(id);
             return Ok(());
         }
 
         Err(String::from("The specified session does not exist"))
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::thread;
+
+    use evaluation::{montecarlo::MonteCarloSession, Heuristic};
+
+    use super::*;
+
+    pub(crate) fn store(millis: u64) -> SessionStore {
+        SessionStore::new(
+            move |b| {
+                AnyEvaluationSession::MonteCarlo(MonteCarloSession::new(
+                    b,
+                    Duration::from_millis(millis),
+                ))
+            },
+            16,
+        )
+    }
+
+    pub(crate) fn empty(store: &SessionStore, size: u8) -> usize {
+        let board = Board::from_rep(
+            ".".repeat(size as usize * size as usize),
+            size,
+            Turn::Black,
+            0.5,
+        )
+        .unwrap();
+        store.create_session_from_board(board).session_id
+    }
+
+    #[test]
+    fn concurrent_moves_are_both_applied() {
+        let store = store(10);
+        let id = empty(&store, 5);
+
+        thread::scope(|s| {
+            for mv in [Move::Coords((0, 0)), Move::Coords((4, 4))] {
+                let store = &store;
+                s.spawn(move || {
+                    store
+                        .modify_session(&id, |session| session.apply_move(mv))
+                        .unwrap()
+                        .unwrap()
+                });
+            }
+        });
+
+        let session = store.get_session(&id).unwrap();
+        assert_eq!(session.board().history.len(), 2);
+        assert_eq!(session.move_metadata.len(), 2);
+    }
+
+    #[test]
+    fn moves_record_their_metadata_in_order() {
+        let store = store(10);
+        let id = empty(&store, 5);
+        let first = Move::Coords((0, 0));
+        store
+            .modify_session(&id, |session| {
+                session.evaluation_cache = Some((Duration::ZERO, vec![(first, 3.0)]));
+                session.apply_move(first)?;
+                session.apply_move(Move::Coords((4, 4)))
+            })
+            .unwrap()
+            .unwrap();
+
+        let metadata = store.get_session(&id).unwrap().move_metadata;
+        assert_eq!(metadata.len(), 2);
+        assert!(metadata[0].timestamp > Duration::ZERO);
+        assert!(metadata[0].timestamp <= metadata[1].timestamp);
+        assert_eq!(metadata[0].evaluation, Some(3.0));
+        assert_eq!(metadata[1].evaluation, None);
+    }
+
+    #[test]
+    fn heuristic_modes_score_the_same_board_differently() {
+        let store = store(10);
+        let score = |heuristic| {
+            let data = BoardData {
+                rep: ".....O......X............".to_string(),
+                size: 5,
+                turn: Turn::White,
+                komi: 0.5,
+                heuristic,
+                strength: None,
+                move_restriction: None,
+            };
+            let id = store.create_new_session(&data).unwrap().session_id;
+            store
+                .get_session(&id)
+                .unwrap()
+                .board()
+                .calculate_heuristic()
+        };
+
+        let strict = score(HeuristicMode::AreaStrict);
+        assert_ne!(strict, score(HeuristicMode::AreaLiberties));
+        assert_ne!(strict, score(HeuristicMode::Influence));
+    }
+
+    #[test]
+    fn komi_changes_shift_the_score_by_the_delta() {
+        let store = store(10);
+        let id = empty(&store, 5);
+        let score = |store: &SessionStore| {
+            store
+                .get_session(&id)
+                .unwrap()
+                .board()
+                .calculate_heuristic()
+        };
+        let before = score(&store);
+
+        store
+            .modify_session(&id, |session| {
+                session.evaluation_cache = Some((Duration::ZERO, Vec::new()));
+                session.set_komi(7.5)
+            })
+            .unwrap()
+            .unwrap();
+        assert_eq!(score(&store), before - 7.0);
+        assert!(store.get_session(&id).unwrap().evaluation_cache.is_none());
+
+        let rejected = store.modify_session(&id, |session| session.set_komi(f32::INFINITY));
+        assert!(matches!(rejected, Ok(Err(BoardError::InvalidKomi(_)))));
+        assert_eq!(score(&store), before - 7.0);
+    }
+
+    #[test]
+    fn reset_returns_to_the_created_board() {
+        let store = store(10);
+        let id = empty(&store, 5);
+        let created = store.get_session(&id).unwrap().board().get_rep();
+
+        store
+            .modify_session(&id, |session| {
+                for mv in [(0, 1), (0, 0), (1, 0), (4, 4)] {
+                    session.apply_move(Move::Coords(mv))?;
+                }
+                session.evaluation_cache = Some((Duration::ZERO, Vec::new()));
+                session.reset()
+            })
+            .unwrap()
+            .unwrap();
+
+        let session = store.get_session(&id).unwrap();
+        assert_eq!(session.board().get_rep(), created);
+        assert!(session.board().history.is_empty());
+        assert!(session.move_metadata.is_empty());
+        assert!(session.evaluation_cache.is_none());
+    }
+
+    #[test]
+    fn reset_keeps_the_imported_moves() {
+        let store = store(10);
+        let board = Board::from_sgf("(;SZ[5]KM[5.5];B[cc];W[dc];B[cd])").unwrap();
+        let imported = (board.get_rep(), board.history.len());
+        let id = store.create_session_from_board(board).session_id;
+
+        store
+            .modify_session(&id, |session| {
+                session.apply_move(Move::Coords((0, 0)))?;
+                session.apply_move(Move::Coords((4, 4)))?;
+                session.reset()
+            })
+            .unwrap()
+            .unwrap();
+
+        let session = store.get_session(&id).unwrap();
+        let board = session.board();
+        assert_eq!((board.get_rep(), board.history.len()), imported);
+        assert_eq!(session.move_metadata.len(), 3);
+    }
+
+    #[test]
+    fn failed_moves_leave_the_session_as_it_was() {
+        let store = store(10);
+        let id = empty(&store, 5);
+        store
+            .modify_session(&id, |session| session.apply_move(Move::Coords((0, 0))))
+            .unwrap()
+            .unwrap();
+
+        let result = store
+            .modify_session(&id, |session| session.apply_move(Move::Coords((0, 0))))
+            .unwrap();
+        assert!(matches!(result, Err(BoardError::Occupied(_))));
+        let session = store.get_session(&id).unwrap();
+        assert_eq!(session.board().history.len(), 1);
+        assert_eq!(session.move_metadata.len(), 1);
+    }
+
+    fn tree_size(store: &SessionStore, id: &usize) -> usize {
+        match store.get_session(id).unwrap().evaluation_session {
+            AnyEvaluationSession::MonteCarlo(mc) => mc.tree_size(),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn consecutive_searches_grow_the_same_tree() {
+        let store = store(200);
+        let id = empty(&store, 5);
+        let handle = store.session_handle(&id).unwrap();
+        let board = store.get_session(&id).unwrap().board().clone();
+
+        Session::search_in_place(&handle, &board).unwrap().unwrap();
+        let first = tree_size(&store, &id);
+        Session::search_in_place(&handle, &board).unwrap().unwrap();
+        let second = tree_size(&store, &id);
+
+        assert!(first > 1);
+        assert!(second > first);
+        assert_eq!(store.get_session(&id).unwrap().searches, 2);
+    }
+
+    #[test]
+    fn sessions_are_readable_during_a_search() {
+        let store = store(300);
+        let id = empty(&store, 5);
+        let handle = store.session_handle(&id).unwrap();
+        let board = store.get_session(&id).unwrap().board().clone();
+
+        thread::scope(|s| {
+            let search = s.spawn(|| Session::search_in_place(&handle, &board));
+            while store.get_session(&id).unwrap().searches == 0 {
+                thread::yield_now();
+            }
+            let start = Instant::now();
+            store.get_session(&id).unwrap();
+            assert!(start.elapsed() < Duration::from_millis(100));
+            search.join().unwrap().unwrap().unwrap();
+        });
+    }
+
+    #[test]
+    fn searches_from_before_a_move_are_dropped() {
+        let store = store(10);
+        let id = empty(&store, 5);
+        let board = store.get_session(&id).unwrap().board().clone();
+        store
+            .modify_session(&id, |session| session.apply_move(Move::Coords((0, 0))))
+            .unwrap()
+            .unwrap();
+
+        let handle = store.session_handle(&id).unwrap();
+        assert!(Session::search_in_place(&handle, &board).is_none());
+    }
+}